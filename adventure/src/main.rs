@@ -1,31 +1,295 @@
-use assets_manager::{asset::Png, AssetCache};
+use assets_manager::asset::Png;
 use frenderer::{
-    input::{Input, Key},
     sprites::{Camera2D, SheetRegion, Transform},
-    wgpu, Immediate, Renderer,
+    wgpu, Immediate,
 };
-use rand::Rng;
 use std::collections::VecDeque;
 use std::str::FromStr;
 
-use engine::{grid::Grid, *};
+use engine::actions::{ActionMap, TurnBuffer};
+use engine::ai;
+use engine::app::{AppConfig, Assets, GameRng, SpriteSink};
+use engine::collision;
+use engine::grid::{Coord, Grid};
+use engine::input::Input;
+use engine::save::{SaveError, Snapshot, Storage};
+use engine::scene::{Scene, SceneStack, Transition};
+use engine::text::{Align, Font};
 use engine::{level::Level, *};
 
+/// Entities that can start in a level file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Entity {
+    /// Where a rider spawns, in player order
+    Tron,
+}
+
+impl FromStr for Entity {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "tron" | "player" => Ok(Entity::Tron),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What players can do, bound to keys in controls.txt.
+/// Every player has their own turns; anyone can use the menus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Confirm,
+    Pause,
+    /// Turns bots on or off from the title screen
+    Bots,
+    /// Picks how many players there are on the title screen
+    Players(usize),
+}
+
+impl FromStr for Action {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "left" => Ok(Action::Left),
+            "right" => Ok(Action::Right),
+            "up" => Ok(Action::Up),
+            "down" => Ok(Action::Down),
+            "confirm" => Ok(Action::Confirm),
+            "pause" => Ok(Action::Pause),
+            "bots" => Ok(Action::Bots),
+            "players2" => Ok(Action::Players(2)),
+            "players3" => Ok(Action::Players(3)),
+            "players4" => Ok(Action::Players(4)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which way each turning action steers, in the order they're queued
+const TURNS: [(Action, Dir); 4] = [
+    (Action::Left, Dir::Left),
+    (Action::Right, Dir::Right),
+    (Action::Up, Dir::Up),
+    (Action::Down, Dir::Down),
+];
+
 struct Game {
-    started: bool,
-    player1: Tron,
-    player2: Tron,
-    level: Level,
+    scenes: SceneStack<Screen>,
+    players: Vec<Tron>,
+    /// How many riders the next round starts with
+    player_count: usize,
+    /// Whether everyone but player 1 is steered by the computer
+    bots: bool,
+    level: Level<Entity>,
+    /// Every pixel a trail head would crash into a wall at, by
+    /// [`Game::pixel_cell`]
+    wall_cells: Grid<bool>,
+    score: MatchScore,
+    actions: ActionMap<Action>,
+    storage: Storage,
+    /// The round in progress when we last quit, resumed instead of
+    /// starting a new match
+    saved: Option<Snapshot>,
+    font: Font,
     frame_counter: usize,
     move_interval: usize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Screen {
+    Title,
+    Playing,
+    Paused,
+    RoundOver(RoundOutcome),
+    MatchOver { winner: usize },
+}
+
+impl Scene for Screen {
+    type Context = Game;
+    fn enter(&mut self, game: &mut Game) {
+        if *self == Screen::Playing {
+            match game.saved.take().map(|snapshot| game.restore(&snapshot)) {
+                Some(Ok(())) => {}
+                Some(Err(e)) => {
+                    eprintln!("Couldn't resume saved match: {e}");
+                    game.restart();
+                }
+                None => game.restart(),
+            }
+        }
+    }
+    fn simulate(&mut self, game: &mut Game, input: &Input, _dt: f32) -> Transition<Self> {
+        let confirm = game.actions.any_pressed(input, Action::Confirm);
+        let pause = game.actions.any_pressed(input, Action::Pause);
+        let count =
+            (2..=MAX_PLAYERS).find(|&n| game.actions.any_pressed(input, Action::Players(n)));
+        match self {
+            Screen::Title if count.is_some() => {
+                // a different line-up is a new match
+                game.saved = None;
+                game.set_player_count(count.unwrap());
+                Transition::Stay
+            }
+            Screen::Title if game.actions.any_pressed(input, Action::Bots) => {
+                game.saved = None;
+                game.bots = !game.bots;
+                game.restart();
+                Transition::Stay
+            }
+            Screen::Title | Screen::RoundOver(_) if confirm => Transition::Replace(Screen::Playing),
+            Screen::MatchOver { .. } if confirm => {
+                game.score.reset();
+                Transition::Replace(Screen::Playing)
+            }
+            Screen::Playing if pause => Transition::Push(Screen::Paused),
+            Screen::Playing => match game.play(input) {
+                None => Transition::Stay,
+                Some(outcome) => {
+                    game.score.record(outcome);
+                    match game.score.winner() {
+                        Some(winner) => Transition::Replace(Screen::MatchOver { winner }),
+                        None => Transition::Replace(Screen::RoundOver(outcome)),
+                    }
+                }
+            },
+            Screen::Paused if pause => Transition::Pop,
+            _ => Transition::Stay,
+        }
+    }
+    fn render(&self, game: &Game, frend: &mut impl SpriteSink) {
+        match self {
+            Screen::Title => {
+                game.draw_world(frend);
+                game.draw_message(
+                    frend,
+                    &format!(
+                        "TRON\n\n{} PLAYERS\n2-4 TO CHANGE\nB: BOTS {}\n\nSPACE TO {}",
+                        game.player_count,
+                        if game.bots { "ON" } else { "OFF" },
+                        if game.saved.is_some() {
+                            "CONTINUE"
+                        } else {
+                            "START"
+                        }
+                    ),
+                );
+            }
+            Screen::Playing => {
+                game.draw_world(frend);
+                game.draw_hud(frend);
+            }
+            Screen::Paused => game.draw_message(frend, "PAUSED"),
+            Screen::RoundOver(outcome) => {
+                game.draw_world(frend);
+                game.draw_hud(frend);
+                let result = match outcome {
+                    RoundOutcome::Winner(p) => format!("PLAYER {} WINS", p + 1),
+                    RoundOutcome::Draw => "DRAW!".to_string(),
+                };
+                game.draw_message(frend, &format!("{result}\n\nSPACE FOR NEXT ROUND"));
+            }
+            Screen::MatchOver { winner } => {
+                game.draw_world(frend);
+                game.draw_hud(frend);
+                game.draw_message(
+                    frend,
+                    &format!(
+                        "PLAYER {} WINS\nTHE MATCH!\n\nSPACE TO PLAY AGAIN",
+                        winner + 1
+                    ),
+                );
+            }
+        }
+    }
+    fn is_overlay(&self) -> bool {
+        *self == Screen::Paused
+    }
+}
+
+/// How a round ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RoundOutcome {
+    /// The player at this index was the last one riding
+    Winner(usize),
+    /// Everyone left crashed on the same move, e.g. by meeting head-on
+    Draw,
+}
+
+impl RoundOutcome {
+    /// The outcome given which players are still riding, or None if
+    /// more than one is.
+    fn from_riding(riding: &[bool]) -> Option<Self> {
+        let mut riders = (0..riding.len()).filter(|&p| riding[p]);
+        match (riders.next(), riders.next()) {
+            (None, _) => Some(RoundOutcome::Draw),
+            (Some(p), None) => Some(RoundOutcome::Winner(p)),
+            _ => None,
+        }
+    }
+}
+
+/// Rounds won by each player in a best-of-`best_of` match.  Draws
+/// don't count towards anyone.
+struct MatchScore {
+    wins: Vec<usize>,
+    draws: usize,
+    best_of: usize,
+}
+
+impl MatchScore {
+    fn new(players: usize, best_of: usize) -> Self {
+        Self {
+            wins: vec![0; players],
+            draws: 0,
+            best_of,
+        }
+    }
+    fn record(&mut self, outcome: RoundOutcome) {
+        match outcome {
+            RoundOutcome::Winner(p) => self.wins[p] += 1,
+            RoundOutcome::Draw => self.draws += 1,
+        }
+    }
+    /// The player who has won more than half of the rounds, if anyone has
+    fn winner(&self) -> Option<usize> {
+        self.wins.iter().position(|&w| w > self.best_of / 2)
+    }
+    fn reset(&mut self) {
+        self.wins.fill(0);
+        self.draws = 0;
+    }
+}
+
 struct Tron {
     dir: Dir,
     body: VecDeque<Vec2>,
+    trail: SheetRegion,
+    /// Turns pressed since the rider last moved
+    turns: TurnBuffer,
+    /// Steered by [`ai::roomiest_dir`] instead of its controls
+    bot: bool,
+    crashed: bool,
 }
 
 impl Tron {
+    /// A rider at `pos` heading `dir`, with a short trail behind it
+    fn new(pos: Vec2, dir: Dir, trail: SheetRegion) -> Self {
+        let body = (0..START_LEN)
+            .map(|i| pos - dir.to_vec2() * (i * TILE_SZ) as f32)
+            .collect();
+        Self {
+            dir,
+            body,
+            trail,
+            turns: TurnBuffer::default(),
+            bot: false,
+            crashed: false,
+        }
+    }
     pub fn transform(&self, index: usize) -> Transform {
         Transform {
             x: self.body.get(index).unwrap().x,
@@ -35,6 +299,18 @@ impl Tron {
             rot: 0.0,
         }
     }
+    /// The area a trail sprite centered at `pos` covers
+    fn head_rect(pos: Vec2) -> Rect {
+        Rect {
+            x: pos.x - 2.0,
+            y: pos.y - 2.0,
+            w: 4,
+            h: 4,
+        }
+    }
+    fn next_head(&self) -> Vec2 {
+        *self.body.front().expect("Tron body is empty") + self.dir.to_vec2()
+    }
 }
 
 const TILE_SZ: usize = 4;
@@ -42,94 +318,124 @@ const TILE_SZ: usize = 4;
 const W: usize = 120;
 const H: usize = 120;
 const DT: f32 = 1.0 / 60.0;
+const MAX_PLAYERS: usize = 4;
+/// Trail pieces each rider starts with
+const START_LEN: usize = 5;
+/// Rounds in a match; the first player to win most of them wins it
+const BEST_OF: usize = 5;
+
+// font.png: 3x5 glyphs for ' ' through 'Z' in 4x6 cells
+const FONT_REGION: SheetRegion = SheetRegion::rect(0, 0, 64, 24);
+const TEXT_GROUP: usize = 1;
+
+const HEAD: SheetRegion = SheetRegion::rect(190, 345, 4, 4);
+/// Each player's trail colour: teal, yellow, green and dark green
+const TRAILS: [SheetRegion; MAX_PLAYERS] = [
+    SheetRegion::rect(190, 395, 4, 4),
+    SheetRegion::rect(738, 172, 4, 4),
+    SheetRegion::rect(34, 0, 4, 4),
+    SheetRegion::rect(188, 18, 4, 4),
+];
 
-struct Contact {
-    rect_a: Rect,
-    index_a: usize,
-    rect_b: Rect,
-    index_b: usize,
-    overlap: Vec2,
+/// How many `tron` starts `level` has
+fn tron_starts(level: &Level<Entity>) -> usize {
+    level
+        .starts()
+        .iter()
+        .filter(|(e, _)| *e == Entity::Tron)
+        .count()
 }
 
-const TRON: [SheetRegion; 2] = [
-    SheetRegion::rect(190, 345, 4, 4),
-    SheetRegion::rect(190, 395, 4, 4),
-];
+/// Checks there are starts and controls for at least two riders, since
+/// a match takes two
+fn check_line_up(level: &Level<Entity>, actions: &ActionMap<Action>) -> Result<(), String> {
+    let starts = tron_starts(level);
+    if starts < 2 {
+        return Err(format!(
+            "level.txt has {starts} tron starts, not two or more"
+        ));
+    }
+    let players = actions.len();
+    if players < 2 {
+        return Err(format!(
+            "controls.txt binds {players} players, not two or more"
+        ));
+    }
+    Ok(())
+}
 
 fn main() {
-    #[cfg(not(target_arch = "wasm32"))]
-    let source =
-        assets_manager::source::FileSystem::new("content").expect("Couldn't load resources");
-    #[cfg(target_arch = "wasm32")]
-    let source = assets_manager::source::Embedded::from(assets_manager::source::embed!("content"));
-    let cache = assets_manager::AssetCache::with_source(source);
-
-    let drv = frenderer::Driver::new(
-        winit::window::WindowBuilder::new()
-            .with_title("test")
-            .with_inner_size(winit::dpi::LogicalSize::new(1024.0, 768.0)),
-        Some((W as u32, H as u32)),
+    engine::app::run::<Game>(
+        AppConfig {
+            title: "Tron",
+            render_size: (W as u32, H as u32),
+            dt: DT,
+            content: engine::content!(),
+            ..AppConfig::default()
+        }
+        .with_args(),
     );
+}
 
-    let mut input = Input::default();
-
-    let mut now = frenderer::clock::Instant::now();
-    let mut acc = 0.0;
-    drv.run_event_loop::<(), _>(
-        move |window, frend| {
-            let mut frend = Immediate::new(frend);
-            let game = Game::new(&mut frend, cache);
-            (window, game, frend)
-        },
-        move |event, target, (window, ref mut game, ref mut frend)| {
-            use winit::event::{Event, WindowEvent};
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    target.exit();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::Resized(size),
-                    ..
-                } => {
-                    if !frend.gpu().is_web() {
-                        frend.resize_surface(size.width, size.height);
-                    }
-                    window.request_redraw();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::RedrawRequested,
-                    ..
-                } => {
-                    let elapsed = now.elapsed().as_secs_f32();
-                    // You can add the time snapping/death spiral prevention stuff here if you want.
-                    // I'm not using it here to keep the starter code small.
-                    acc += elapsed;
-                    now = std::time::Instant::now();
-                    // While we have time to spend
-                    while acc >= DT {
-                        // simulate a frame
-                        acc -= DT;
-                        game.simulate(&input, DT);
-                        input.next_frame();
-                    }
-                    game.render(frend);
-                    frend.render();
-                    window.request_redraw();
-                }
-                event => {
-                    input.process_input_event(&event);
-                }
+impl engine::app::Game for Game {
+    fn new(cache: &Assets, _rng: GameRng, mut storage: Storage) -> Self {
+        // Ayelet: Changed this to be only one level
+        let level = Level::parse(
+            &cache
+                .load::<String>("level")
+                .expect("Couldn't access level.txt")
+                .read(),
+        )
+        .unwrap_or_else(|e| panic!("Couldn't parse level.txt: {e}"));
+        let actions = ActionMap::load(cache, "controls");
+        check_line_up(&level, &actions).unwrap_or_else(|e| panic!("Couldn't set up a match: {e}"));
+        let mut game = Game {
+            scenes: SceneStack::default(),
+            players: vec![],
+            player_count: 2,
+            bots: false,
+            wall_cells: Grid::new(0, 0, []),
+            level,
+            score: MatchScore::new(2, BEST_OF),
+            actions,
+            saved: storage.load_snapshot(SNAPSHOT_NAME, SNAPSHOT_VERSION),
+            storage,
+            font: Font::fixed_width(FONT_REGION, ' '..='Z', 4, 6),
+            frame_counter: 0,
+            move_interval: 5,
+        };
+        let map_w = game.level.width() * game.level.tile_size();
+        let map_h = game.level.height() * game.level.tile_size();
+        game.wall_cells = Grid::new(
+            map_w,
+            map_h,
+            (0..map_w * map_h).map(|i| game.hits_wall(game.cell_pixel((i % map_w, i / map_w)))),
+        );
+        if let Some(saved) = &game.saved {
+            // show the saved line-up on the title screen
+            let count = saved.get("players").unwrap_or(game.player_count);
+            game.bots = saved.get("bots").unwrap_or(false);
+            game.set_player_count(count);
+        }
+        game.restart();
+        game.scenes = SceneStack::new(Screen::Title, &mut game);
+        game
+    }
+
+    /// Saves the round in progress to pick up next time.  Otherwise the
+    /// last save is forgotten, unless it's still waiting to be continued.
+    fn quit(&mut self) {
+        let playing = matches!(self.scenes.top(), Some(Screen::Playing | Screen::Paused));
+        if playing {
+            if let Err(e) = self.storage.save_snapshot(&self.snapshot()) {
+                eprintln!("Couldn't save match: {e}");
             }
-        },
-    )
-    .expect("event loop error");
-}
+        } else if self.saved.is_none() {
+            self.storage.clear_snapshot();
+        }
+    }
 
-impl Game {
-    fn new(renderer: &mut Immediate, cache: AssetCache) -> Self {
+    fn init_graphics(&mut self, renderer: &mut Immediate, cache: &Assets) {
         let tile_handle = cache
             .load::<Png>("tilesheet")
             .expect("Couldn't load tilesheet img");
@@ -140,165 +446,187 @@ impl Game {
             tile_img.dimensions(),
             Some("tiles-sprites"),
         );
-        // Ayelet: Changed this to be only one level
-        let level = engine::level::Level::from_str(
-            &cache
-                .load::<String>("level")
-                .expect("Couldn't access level.txt")
-                .read(),
-        );
-        // let current_level = 0; // For future if we want to add more levels?
         let camera = Camera2D {
             screen_pos: [0.0, 0.0],
             screen_size: [W as f32, H as f32],
         };
-        let sprite_estimate = level.sprite_count() + level.starts().len();
+        let sprite_estimate = self.level.sprite_count() + MAX_PLAYERS * START_LEN;
         renderer.sprite_group_add(
             &tile_tex,
             vec![Transform::ZERO; sprite_estimate],
             vec![SheetRegion::ZERO; sprite_estimate],
             camera,
         );
-        let mut player1_body: VecDeque<Vec2> = VecDeque::new();
-        let mut player2_body: VecDeque<Vec2> = VecDeque::new();
-        for i in 0i8..5 {
-            let i = f32::from(i);
-            player1_body.push_back(Vec2 {
-                x: 100.0 + i * 4.0,
-                y: 100.0,
-            })
-        }
-        for i in 0i8..5 {
-            let i = f32::from(i);
-            player2_body.push_back(Vec2 {
-                x: 150.0 + i * 4.0,
-                y: 150.0,
-            })
-        }
-        let mut game = Game {
-            started: true,
-            player1: Tron {
-                dir: (Dir::Right),
-                body: (player1_body),
-            },
-            player2: Tron {
-                dir: (Dir::Right),
-                body: (player2_body),
-            },
-            level: level,
-            frame_counter: 0,
-            move_interval: 5,
-        };
-        game
+        let font_handle = cache.load::<Png>("font").expect("Couldn't load font img");
+        let font_img = font_handle.read().0.to_rgba8();
+        let font_tex = renderer.create_array_texture(
+            &[&font_img],
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            font_img.dimensions(),
+            Some("font-sprites"),
+        );
+        renderer.sprite_group_add(&font_tex, vec![], vec![], camera);
     }
 
-    fn render(&mut self, frend: &mut Immediate) {
-        self.level.render_immediate(frend);
+    fn render(&mut self, frend: &mut impl SpriteSink) {
+        let scenes = std::mem::take(&mut self.scenes);
+        scenes.render(self, frend);
+        self.scenes = scenes;
+    }
 
-        let mut count: usize = 0;
-        frend.draw_sprite(0, self.player1.transform(count), TRON[0]);
-        frend.draw_sprite(0, self.player2.transform(count), TRON[0]);
-
-        
-        for vector2 in self.player1.body.iter() {
-            if count == 0 {
-                count = count + 1;
-                continue;
-            };
-            frend.draw_sprite(0, self.player1.transform(count), TRON[1]);
-            count = count + 1;
-        }
+    fn simulate(&mut self, input: &Input, dt: f32) {
+        let mut scenes = std::mem::take(&mut self.scenes);
+        scenes.simulate(self, input, dt);
+        self.scenes = scenes;
+    }
+}
 
-        count = 0;
-        for vector2 in self.player2.body.iter() {
-            if count == 0 {
-                count = count + 1;
-                continue;
-            };
-            frend.draw_sprite(0, self.player2.transform(count), TRON[1]);
-            count = count + 1;
+impl Game {
+    fn draw_world(&self, frend: &mut impl SpriteSink) {
+        self.level.render_immediate(frend);
+        for rider in &self.players {
+            frend.draw_sprite(0, rider.transform(0), HEAD);
+            for i in 1..rider.body.len() {
+                frend.draw_sprite(0, rider.transform(i), rider.trail);
+            }
         }
     }
 
-    fn simulate(&mut self, input: &Input, dt: f32) {
-        self.frame_counter += 1;
-        if self.frame_counter >= self.move_interval {
-
-            // arrow keys for player 1
-            if input.is_key_down(Key::ArrowLeft) && self.player1.dir != Dir::Right {
-                self.player1.dir = Dir::Left;
-            } else if input.is_key_down(Key::ArrowRight) && self.player1.dir != Dir::Left {
-                self.player1.dir = Dir::Right;
-            } else if input.is_key_down(Key::ArrowUp) && self.player1.dir != Dir::Down {
-                self.player1.dir = Dir::Up;
-            } else if input.is_key_down(Key::ArrowDown) && self.player1.dir != Dir::Up {
-                self.player1.dir = Dir::Down;
-            }
+    /// Each player's round wins, in the corners of the screen
+    fn draw_hud(&self, frend: &mut impl SpriteSink) {
+        let corners = [
+            (Align::Left, Vec2::new(1.0, H as f32 - 1.0)),
+            (Align::Right, Vec2::new(W as f32 - 1.0, H as f32 - 1.0)),
+            (Align::Left, Vec2::new(1.0, 7.0)),
+            (Align::Right, Vec2::new(W as f32 - 1.0, 7.0)),
+        ];
+        for (p, (wins, (align, pos))) in self.score.wins.iter().zip(corners).enumerate() {
+            self.font.draw(
+                frend,
+                TEXT_GROUP,
+                &format!("P{} {wins}", p + 1),
+                pos,
+                align,
+                1.0,
+            );
+        }
+    }
 
-            // wasd for player 2
-            if input.is_key_down(Key::KeyA) && self.player2.dir != Dir::Right {
-                self.player2.dir = Dir::Left;
-            } else if input.is_key_down(Key::KeyD) && self.player2.dir != Dir::Left {
-                self.player2.dir = Dir::Right;
-            } else if input.is_key_down(Key::KeyW) && self.player2.dir != Dir::Down {
-                self.player2.dir = Dir::Up;
-            } else if input.is_key_down(Key::KeyS) && self.player2.dir != Dir::Up {
-                self.player2.dir = Dir::Down;
-            }
-            let head_pos1 = self
-                .player1
-                .body
-                .front()
-                .expect("Tron body is empty")
-                .clone();
-            let new_head_pos1 = head_pos1 + self.player1.dir.to_vec2();
-            let head_pos2 = self
-                .player2
-                .body
-                .front()
-                .expect("Tron body is empty")
-                .clone();
-            let new_head_pos2 = head_pos2 + self.player2.dir.to_vec2();
-
-            // coliision with the wall - restart game
-            if new_head_pos1.x < 0.0
-                || new_head_pos1.y < 0.0
-                || new_head_pos1.x >= W as f32
-                || new_head_pos1.y >= H as f32
-                || new_head_pos2.y < 0.0
-                || new_head_pos2.x >= W as f32
-                || new_head_pos2.y >= H as f32
-                || new_head_pos2.x < 0.0
-            {
-                self.restart();
-                return;
-            }
+    /// Centered lines of text in the middle of the screen
+    fn draw_message(&self, frend: &mut impl SpriteSink, text: &str) {
+        let height = self.font.measure(text, 1.0).y;
+        self.font.draw(
+            frend,
+            TEXT_GROUP,
+            text,
+            Vec2::new(W as f32 / 2.0, (H as f32 + height) / 2.0),
+            Align::Center,
+            1.0,
+        );
+    }
 
-            // self collisions
-            if self.player1.body.contains(&new_head_pos1) {
-                self.restart();
-                return;
+    /// Steers and moves every rider still in the round.  Returns how
+    /// the round ended once at most one is left.
+    fn play(&mut self, input: &Input) -> Option<RoundOutcome> {
+        for (p, rider) in self.players.iter_mut().enumerate() {
+            if !rider.bot && !rider.crashed {
+                let controls = self.actions.player(p);
+                rider.turns.record(rider.dir, controls, input, &TURNS);
             }
-            if self.player2.body.contains(&new_head_pos2) {
-                self.restart();
-                return;
+        }
+        self.frame_counter += 1;
+        if self.frame_counter < self.move_interval {
+            return None;
+        }
+        self.frame_counter = 0;
+        if self.players.iter().any(|r| r.bot && !r.crashed) {
+            let blocked = self.blocked_cells();
+            for rider in self.players.iter_mut().filter(|r| r.bot && !r.crashed) {
+                let Some(cell) = Self::pixel_cell(&blocked, rider.body[0]) else {
+                    continue;
+                };
+                if let Some(dir) = ai::roomiest_dir(&blocked, cell, rider.dir, |b| !*b) {
+                    rider.dir = dir;
+                }
             }
-
-            // collisions between players
-            if self.player1.body.contains(&new_head_pos2) {
-                self.restart();
-                return;
+        }
+        for rider in self.players.iter_mut().filter(|r| !r.bot && !r.crashed) {
+            rider.dir = rider.turns.next_turn(rider.dir);
+        }
+        let heads: Vec<Option<Vec2>> = self
+            .players
+            .iter()
+            .map(|r| (!r.crashed).then(|| r.next_head()))
+            .collect();
+        // crash into walls, the edge of the map, or any light trail, even
+        // those of riders who are already out
+        let crashes: Vec<bool> = heads
+            .iter()
+            .enumerate()
+            .map(|(p, head)| {
+                let Some(head) = head else {
+                    return false;
+                };
+                self.hits_wall(*head)
+                    || self.players.iter().any(|r| r.body.contains(head))
+                    // moving into the same spot takes both riders out
+                    || heads
+                        .iter()
+                        .enumerate()
+                        .any(|(q, other)| q != p && *other == Some(*head))
+            })
+            .collect();
+        for ((rider, head), crashed) in self.players.iter_mut().zip(heads).zip(crashes) {
+            if crashed {
+                rider.crashed = true;
+            } else if let Some(head) = head {
+                rider.body.push_front(head);
             }
-            if self.player2.body.contains(&new_head_pos1) {
-                self.restart();
-                return;
+        }
+        let riding: Vec<bool> = self.players.iter().map(|r| !r.crashed).collect();
+        RoundOutcome::from_riding(&riding)
+    }
+    /// The cell of a per-pixel grid like `wall_cells` that a trail at
+    /// `pos` is in.  Rows count down from the top of the map, as for
+    /// level grids.
+    fn pixel_cell<T>(grid: &Grid<T>, pos: Vec2) -> Option<Coord> {
+        let row = grid.height() as f32 - 1.0 - pos.y;
+        (pos.x >= 0.0 && row >= 0.0 && grid.contains(pos.x as usize, row as usize))
+            .then_some((pos.x as usize, row as usize))
+    }
+    /// Where a trail in per-pixel grid cell `cell` is
+    fn cell_pixel(&self, (x, row): Coord) -> Vec2 {
+        let map_h = self.level.height() * self.level.tile_size();
+        Vec2::new(x as f32, (map_h - 1 - row) as f32)
+    }
+    /// Every pixel a trail head can't move into: walls and every light trail
+    fn blocked_cells(&self) -> Grid<bool> {
+        let walls = &self.wall_cells;
+        let mut blocked = Grid::new(
+            walls.width(),
+            walls.height(),
+            (0..walls.width() * walls.height()).map(|i| walls[i]),
+        );
+        for pos in self.players.iter().flat_map(|r| &r.body) {
+            if let Some((x, y)) = Self::pixel_cell(&blocked, *pos) {
+                *blocked.get_mut(x, y).unwrap() = true;
             }
-
-            self.player1.body.push_front(new_head_pos1);
-            self.player2.body.push_front(new_head_pos2);
-
-            self.frame_counter = 0;
         }
+        blocked
+    }
+    /// Would a trail head at `pos` crash into a solid tile or leave the map?
+    fn hits_wall(&self, pos: Vec2) -> bool {
+        let map_w = (self.level.width() * self.level.tile_size()) as f32;
+        let map_h = (self.level.height() * self.level.tile_size()) as f32;
+        if pos.x < 0.0 || pos.y < 0.0 || pos.x >= map_w || pos.y >= map_h {
+            return true;
+        }
+        let mut contacts = vec![];
+        collision::gather_tile_contacts(&[Tron::head_rect(pos)], &self.level, &mut contacts);
+        // just touching a wall is fine, running into it isn't
+        contacts
+            .iter()
+            .any(|c| c.overlap.x > 0.0 && c.overlap.y > 0.0)
     }
 
     // fn relocate_apple(&mut self) {
@@ -314,125 +642,353 @@ impl Game {
     //     }
     // }
 
+    /// Puts a fresh rider on each of the first `player_count` starts,
+    /// heading for the middle of the map
     fn restart(&mut self) {
         self.frame_counter = 0;
-
-        // Reset snake position and direction
-        let initial_snake_length = 5; // for example, start with a length of 5
-        let mut initial_body1: VecDeque<Vec2> = VecDeque::new();
-        let mut initial_body2: VecDeque<Vec2> = VecDeque::new();
-        let start_x1 = W as f32 / 2.0; // Start in the middle of the width
-        let start_y1 = H as f32 / 2.0; // Start in the middle of the height
-        let start_x2 = W as f32 / 4.0 * 3.0; // Start in the middle of the width
-        let start_y2 = H as f32 / 4.0 * 3.0; // Start in the middle of the height
-        for i in 0..initial_snake_length {
-            initial_body1.push_back(Vec2 {
-                x: start_x1 - i as f32 * TILE_SZ as f32,
-                y: start_y1,
-            });
-        }
-        for i in 0..initial_snake_length {
-            initial_body2.push_back(Vec2 {
-                x: start_x2 - i as f32 * TILE_SZ as f32,
-                y: start_y2,
-            });
+        let middle = Vec2::new(
+            (self.level.width() * self.level.tile_size()) as f32 / 2.0,
+            (self.level.height() * self.level.tile_size()) as f32 / 2.0,
+        );
+        let starts = self
+            .level
+            .starts()
+            .iter()
+            .filter(|(e, _)| *e == Entity::Tron)
+            .take(self.player_count);
+        self.players = starts
+            .zip(TRAILS)
+            .map(|((_, pos), trail)| {
+                let to_middle = middle - *pos;
+                let dir = match (to_middle.x.abs() >= to_middle.y.abs(), to_middle) {
+                    (true, v) if v.x < 0.0 => Dir::Left,
+                    (true, _) => Dir::Right,
+                    (false, v) if v.y < 0.0 => Dir::Down,
+                    (false, _) => Dir::Up,
+                };
+                Tron::new(*pos, dir, trail)
+            })
+            .collect();
+        for rider in self.players.iter_mut().skip(1) {
+            rider.bot = self.bots;
         }
-        self.player1 = Tron {
-            dir: Dir::Right, // Starting direction
-            body: initial_body1,
-        };
-        self.player2 = Tron {
-            dir: Dir::Right, // Starting direction
-            body: initial_body2,
-        };
-
     }
 
-    // fn simulate(&mut self, input: &Input, dt: f32) {
-    //     let mut dx = input.key_axis(Key::ArrowLeft, Key::ArrowRight) * DT;
-    //     let mut dy = input.key_axis(Key::ArrowDown, Key::ArrowUp) * DT;
-    // }
+    /// Changes how many riders play, as far as the level has starts and
+    /// controls for, and starts a fresh match score.  There are always
+    /// enough for two, see [`check_line_up`].
+    fn set_player_count(&mut self, count: usize) {
+        let most = tron_starts(&self.level)
+            .min(self.actions.len())
+            .min(MAX_PLAYERS);
+        self.player_count = count.clamp(2, most);
+        self.score = MatchScore::new(self.player_count, BEST_OF);
+        self.restart();
+    }
 
-    // let dest = self.player.pos + Vec2 { x: dx, y: dy };
-    // self.player.pos = dest;
-
-    // function to gather tile-entity contacts
-    // pub fn gather_tile_contacts(rects: &[Rect], level: &Level, contacts: &mut Vec<Contact>) {
-    //     for (i, rect) in rects.iter().enumerate() {
-    //         for (tr, _) in level.tiles_within(*rect).filter(|(_tr, td)| td.solid) {
-    //             if let Some(overlap) = rect.overlap(tr) {
-    //                 contacts.push(Contact {
-    //                     index_a: i,
-    //                     rect_a: *rect,
-    //                     index_b: 0,
-    //                     rect_b: tr,
-    //                     overlap: overlap,
-    //                 })
-    //             }
-    //         }
-    //     }
-    // }
-    // function to gather entity-entity contacts
-    fn gather_contact(a_rects: &[Rect], b_rects: &[Rect], contacts_list: &mut Vec<Contact>) {
-        for (i, a_rect) in a_rects.iter().enumerate() {
-            for (j, b_rect) in b_rects.iter().enumerate() {
-                if let Some(overlap) = a_rect.overlap(*b_rect) {
-                    contacts_list.push(Contact {
-                        index_a: i,
-                        rect_a: *a_rect,
-                        index_b: j,
-                        rect_b: *b_rect,
-                        overlap: overlap,
-                    })
-                }
-            }
+    fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new(SNAPSHOT_NAME, SNAPSHOT_VERSION);
+        snapshot.set("players", self.player_count);
+        snapshot.set("bots", self.bots);
+        snapshot.set_list("wins", &self.score.wins);
+        snapshot.set("draws", self.score.draws);
+        snapshot.set_list("dirs", self.players.iter().map(|r| r.dir));
+        snapshot.set_list("crashed", self.players.iter().map(|r| r.crashed));
+        for (p, rider) in self.players.iter().enumerate() {
+            let trail = rider.body.iter().map(|pos| (pos.x, pos.y));
+            snapshot.set_pairs(&format!("trail{}", p + 1), trail);
         }
+        snapshot
     }
 
-    //create empty vecs for different contacts
-    // let mut pl_contacts: Vec<Contact> = Vec::new();
-
-    // // Get player's Rectengle
-    // let player_rect: Rect = self.player.to_rect();
-
-    // // gather player-tile contacts
-    // gather_tile_contacts(&[player_rect], &self.level(), &mut pl_contacts);
-
-    // // sort player contacts vector
-    // em_contacts.sort_by(|a, b| b.overlap.mag_sq().partial_cmp(&a.overlap.mag_sq()).unwrap());
-
-    // // deal with player-tile contact
-    // for c in pl_contacts.drain(..) {
-    //     let displacement: Vec2 = compute_displacement(self.player.to_rect(), c.rect_b);
-    //     self.player.pos += displacement;
-    // }
-
-    // for c in pl_en_contacts.drain(..) {
-    //     if attacking && !enemies_to_remove.contains(&c.index_b) {
-    //         enemies_to_remove.push(c.index_b);
-    //     }
-    // }
-    // enemies_to_remove.sort();
-    // for index in enemies_to_remove.iter().rev() {
-    //     self.enemies.swap_remove(*index);
-    // }
-
-    fn compute_displacement(a: Rect, b: Rect) -> Vec2 {
-        let Some(mut overlap) = a.overlap(b) else {
-            return Vec2 { x: 0.0, y: 0.0 };
-        };
-        if overlap.y < overlap.x {
-            overlap.x = 0.0;
-        } else {
-            overlap.y = 0.0;
+    /// Picks up the round saved in `snapshot`, as long as it fits this
+    /// level and its starts
+    fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SaveError> {
+        let count: usize = snapshot.get("players")?;
+        let wins: Vec<usize> = snapshot.get_list("wins")?;
+        let dirs: Vec<Dir> = snapshot.get_list("dirs")?;
+        let crashed: Vec<bool> = snapshot.get_list("crashed")?;
+        let mut trails = vec![];
+        for p in 0..count {
+            let trail: VecDeque<Vec2> = snapshot
+                .get_pairs(&format!("trail{}", p + 1))?
+                .into_iter()
+                .map(|(x, y)| Vec2 { x, y })
+                .collect();
+            trails.push(trail);
         }
-        if a.x < b.x {
-            overlap.x *= -1.0;
+        let draws = snapshot.get("draws")?;
+        let bots = snapshot.get("bots")?;
+        let fits = [wins.len(), dirs.len(), crashed.len()] == [count; 3]
+            && trails.iter().all(|t| !t.is_empty());
+        self.bots = bots;
+        self.set_player_count(count);
+        if !fits || self.player_count != count {
+            return Err(SaveError::BadValue {
+                key: "players".to_string(),
+                text: count.to_string(),
+            });
         }
-        if a.y < b.y {
-            overlap.y *= -1.0;
+        self.score.wins = wins;
+        self.score.draws = draws;
+        for (p, rider) in self.players.iter_mut().enumerate() {
+            rider.dir = dirs[p];
+            rider.crashed = crashed[p];
+            rider.body = std::mem::take(&mut trails[p]);
         }
-        return overlap;
+        Ok(())
     }
 }
 
+const SNAPSHOT_NAME: &str = "tron";
+/// Bump whenever what's in a snapshot changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::headless::{Headless, InputScript};
+    use engine::input::Key;
+
+    /// A round past the title screen
+    fn playing() -> Headless<Game> {
+        let mut h = Headless::<Game>::new(engine::content!());
+        h.input_mut().press(Key::Space);
+        h.step();
+        h.input_mut().release(Key::Space);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        h
+    }
+
+    #[test]
+    fn test_trails_grow() {
+        let mut h = playing();
+        let start1 = h.game().players[0].body[0];
+        let start2 = h.game().players[1].body[0];
+        h.run(&InputScript::new().press(h.tick(), Key::KeyW), 10);
+        let game = h.game();
+        assert_eq!(game.players[0].body[0], start1 + Vec2 { x: 2.0, y: 0.0 });
+        assert_eq!(game.players[1].body[0], start2 + Vec2 { x: 0.0, y: 2.0 });
+        // light trails never shrink
+        assert_eq!(game.players[0].body.len(), 7);
+        assert_eq!(game.players[1].body.len(), 7);
+    }
+    #[test]
+    fn test_quick_turns_are_buffered() {
+        let mut h = playing();
+        assert_eq!(h.game().players[0].dir, Dir::Right);
+        let start = h.game().players[0].body[0];
+        let interval = h.game().move_interval;
+        // up then left before the next move: one turn a move, and left
+        // isn't a reversal once up is queued
+        let t = h.tick();
+        let script = InputScript::new()
+            .tap(t, Key::ArrowUp)
+            .tap(t + 1, Key::ArrowLeft)
+            .tap(t + 2, Key::ArrowRight);
+        h.run(&script, interval * 2);
+        let rider = &h.game().players[0];
+        assert_eq!(rider.dir, Dir::Left);
+        assert_eq!(
+            rider.body[0],
+            start + Dir::Up.to_vec2() + Dir::Left.to_vec2()
+        );
+    }
+    #[test]
+    fn test_save_and_resume() {
+        let mut h = playing();
+        h.game_mut().score.wins = vec![1, 0];
+        let interval = h.game().move_interval;
+        h.run(
+            &InputScript::new().tap(h.tick(), Key::ArrowUp),
+            interval * 4,
+        );
+        h.quit();
+        let trails: Vec<VecDeque<Vec2>> = h.game().players.iter().map(|r| r.body.clone()).collect();
+
+        // closing the window on the title screen keeps the save
+        let mut h = Headless::<Game>::with_storage(engine::content!(), h.game().storage.clone());
+        assert!(h.game().saved.is_some());
+        h.quit();
+        let mut h = Headless::<Game>::with_storage(engine::content!(), h.game().storage.clone());
+        assert!(h.game().saved.is_some());
+        h.run(&InputScript::new().tap(h.tick(), Key::Space), 1);
+        let game = h.game();
+        assert_eq!(game.scenes.top(), Some(&Screen::Playing));
+        assert_eq!(game.score.wins, [1, 0]);
+        assert_eq!(game.players[0].dir, Dir::Up);
+        let resumed: Vec<VecDeque<Vec2>> = game.players.iter().map(|r| r.body.clone()).collect();
+        assert_eq!(resumed, trails);
+    }
+    #[test]
+    fn test_walls_are_deadly() {
+        let mut h = playing();
+        let start1 = h.game().players[0].body[0];
+        // the right-hand border wall starts at x = 116
+        h.game_mut().players[0].body = VecDeque::from([Vec2 { x: 112.0, y: 60.0 }]);
+        let interval = h.game().move_interval;
+        h.run(&InputScript::new(), interval * 2);
+        // touching the wall is fine
+        assert_eq!(h.game().players[0].body[0], Vec2 { x: 114.0, y: 60.0 });
+        h.run(&InputScript::new(), interval);
+        // but running into it ends the round, in the other player's favour
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::RoundOver(RoundOutcome::Winner(1)))
+        );
+        assert_eq!(h.game().score.wins, [0, 1]);
+        h.input_mut().press(Key::Space);
+        h.step();
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        assert_eq!(h.game().players[0].body[0], start1);
+    }
+    #[test]
+    fn test_round_outcomes() {
+        assert_eq!(RoundOutcome::from_riding(&[true, true]), None);
+        assert_eq!(
+            RoundOutcome::from_riding(&[false, true]),
+            Some(RoundOutcome::Winner(1))
+        );
+        assert_eq!(
+            RoundOutcome::from_riding(&[false, false]),
+            Some(RoundOutcome::Draw)
+        );
+        // nobody wins while two players are still riding
+        assert_eq!(RoundOutcome::from_riding(&[false, true, true]), None);
+    }
+    #[test]
+    fn test_spawns_from_level() {
+        let mut h = Headless::<Game>::new(engine::content!());
+        h.input_mut().press(Key::Digit3);
+        h.step();
+        h.input_mut().release(Key::Digit3);
+        h.input_mut().press(Key::Space);
+        h.step();
+        let game = h.game();
+        assert_eq!(game.players.len(), 3);
+        assert_eq!(game.score.wins, [0, 0, 0]);
+        let starts = game.level.starts();
+        for (rider, (_, pos)) in game.players.iter().zip(starts) {
+            assert_eq!(rider.body[0], *pos);
+            assert_eq!(rider.body.len(), START_LEN);
+        }
+        // everyone heads for the middle
+        let dirs: Vec<Dir> = game.players.iter().map(|r| r.dir).collect();
+        assert_eq!(dirs, [Dir::Right, Dir::Left, Dir::Down]);
+        // there are only four starts to go around
+        h.game_mut().set_player_count(6);
+        assert_eq!(h.game().players.len(), 4);
+    }
+    #[test]
+    fn test_line_up_needs_two_riders() {
+        let game = Headless::<Game>::new(engine::content!());
+        let (level, actions) = (&game.game().level, &game.game().actions);
+        assert_eq!(check_line_up(level, actions), Ok(()));
+        let one_start =
+            Level::parse("solo 2 1\n====\n. o 0 0 4 4\n====\n. .\n====\ntron 0 0\n").unwrap();
+        assert!(check_line_up(&one_start, actions).is_err());
+        let one_player = ActionMap::parse("1 left ArrowLeft\n").unwrap();
+        assert!(check_line_up(level, &one_player).is_err());
+    }
+    #[test]
+    fn test_last_rider_standing() {
+        let mut h = playing();
+        h.game_mut().set_player_count(3);
+        let interval = h.game().move_interval;
+        // player 3 runs into player 1's trail and is out
+        let tail = *h.game().players[0].body.back().unwrap();
+        h.game_mut().players[2].body = VecDeque::from([tail + Vec2 { x: 0.0, y: 1.0 }]);
+        h.game_mut().players[2].dir = Dir::Down;
+        h.run(&InputScript::new(), interval);
+        let game = h.game();
+        assert!(game.players[2].crashed);
+        assert_eq!(game.scenes.top(), Some(&Screen::Playing));
+        // but the round goes on, with their trail still in the way
+        assert_eq!(game.players[0].body.len(), START_LEN + 1);
+        h.game_mut().players[1].body = VecDeque::from([tail + Vec2 { x: 0.0, y: 2.0 }]);
+        h.game_mut().players[1].dir = Dir::Down;
+        h.run(&InputScript::new(), interval);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::RoundOver(RoundOutcome::Winner(0)))
+        );
+        assert_eq!(h.game().score.wins, [1, 0, 0]);
+    }
+    #[test]
+    fn test_head_on_is_a_draw() {
+        let mut h = playing();
+        let interval = h.game().move_interval;
+        let game = h.game_mut();
+        game.players[0].body = VecDeque::from([Vec2 { x: 60.0, y: 60.0 }]);
+        game.players[1].body = VecDeque::from([Vec2 { x: 62.0, y: 60.0 }]);
+        game.players[1].dir = Dir::Left;
+        h.run(&InputScript::new(), interval);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::RoundOver(RoundOutcome::Draw))
+        );
+        assert_eq!(
+            (h.game().score.wins.as_slice(), h.game().score.draws),
+            (&[0, 0][..], 1)
+        );
+    }
+    #[test]
+    fn test_best_of_match() {
+        let mut h = playing();
+        let interval = h.game().move_interval;
+        h.game_mut().score.wins = vec![BEST_OF / 2 - 1, 0];
+        // player 2 turns back into their own trail
+        let reverse = |game: &mut Game| {
+            game.players[1].body =
+                VecDeque::from([Vec2 { x: 90.0, y: 90.0 }, Vec2 { x: 89.0, y: 90.0 }]);
+            game.players[1].dir = Dir::Left;
+        };
+        reverse(h.game_mut());
+        h.run(&InputScript::new(), interval);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::RoundOver(RoundOutcome::Winner(0)))
+        );
+        // one more round won is a majority
+        h.input_mut().press(Key::Space);
+        h.step();
+        h.input_mut().release(Key::Space);
+        reverse(h.game_mut());
+        h.run(&InputScript::new(), interval);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::MatchOver { winner: 0 })
+        );
+        // and a new match starts from nothing
+        h.input_mut().press(Key::Space);
+        h.step();
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        assert_eq!(h.game().score.wins, [0, 0]);
+    }
+    #[test]
+    fn test_bots_dodge_walls() {
+        let mut h = Headless::<Game>::new(engine::content!());
+        h.input_mut().press(Key::KeyB);
+        h.step();
+        h.input_mut().release(Key::KeyB);
+        h.input_mut().press(Key::Space);
+        h.step();
+        h.input_mut().release(Key::Space);
+        assert!(!h.game().players[0].bot && h.game().players[1].bot);
+        // park player 1 out of the way, and aim the bot at the left wall
+        let game = h.game_mut();
+        game.players[0].body = VecDeque::from([Vec2 { x: 60.0, y: 100.0 }]);
+        game.players[0].dir = Dir::Up;
+        game.players[1].body = VecDeque::from([Vec2 { x: 10.0, y: 40.0 }]);
+        game.players[1].dir = Dir::Left;
+        // player 1 crashes into the top wall long before the bot gives up
+        let interval = h.game().move_interval;
+        h.run(&InputScript::new(), interval * 20);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::RoundOver(RoundOutcome::Winner(1)))
+        );
+        let bot = &h.game().players[1];
+        assert!(bot.body.iter().all(|pos| !h.game().hits_wall(*pos)));
+    }
+}
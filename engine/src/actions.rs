@@ -0,0 +1,527 @@
+use crate::app::Assets;
+use crate::input::{Input, Key};
+use crate::Dir;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// Something a player can press to trigger an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    Key(Key),
+}
+
+impl Control {
+    fn is_down(self, input: &Input) -> bool {
+        match self {
+            Control::Key(key) => input.is_key_down(key),
+        }
+    }
+    fn was_down(self, input: &Input) -> bool {
+        match self {
+            Control::Key(key) => input.was_key_down(key),
+        }
+    }
+}
+
+macro_rules! named {
+    ($fn_name:ident, $name_fn:ident, $ty:ident, [$($name:ident),* $(,)?]) => {
+        fn $fn_name(s: &str) -> Option<$ty> {
+            match s {
+                $(stringify!($name) => Some($ty::$name),)*
+                _ => None,
+            }
+        }
+        fn $name_fn(v: $ty) -> Option<&'static str> {
+            match v {
+                $($ty::$name => Some(stringify!($name)),)*
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+    };
+}
+
+// Key names are the same as the `Key` variants, e.g. KeyA or ArrowLeft.
+named!(
+    key_named,
+    key_name,
+    Key,
+    [
+        KeyA,
+        KeyB,
+        KeyC,
+        KeyD,
+        KeyE,
+        KeyF,
+        KeyG,
+        KeyH,
+        KeyI,
+        KeyJ,
+        KeyK,
+        KeyL,
+        KeyM,
+        KeyN,
+        KeyO,
+        KeyP,
+        KeyQ,
+        KeyR,
+        KeyS,
+        KeyT,
+        KeyU,
+        KeyV,
+        KeyW,
+        KeyX,
+        KeyY,
+        KeyZ,
+        Digit0,
+        Digit1,
+        Digit2,
+        Digit3,
+        Digit4,
+        Digit5,
+        Digit6,
+        Digit7,
+        Digit8,
+        Digit9,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadEnter,
+        ArrowUp,
+        ArrowDown,
+        ArrowLeft,
+        ArrowRight,
+        Space,
+        Enter,
+        Escape,
+        Tab,
+        Backspace,
+        ShiftLeft,
+        ShiftRight,
+        ControlLeft,
+        ControlRight,
+        AltLeft,
+        AltRight,
+        Comma,
+        Period,
+        Slash,
+        Semicolon,
+        Quote,
+        BracketLeft,
+        BracketRight,
+        Minus,
+        Equal,
+    ]
+);
+
+/// Parses a key name like `ArrowLeft`.
+pub fn parse_control(s: &str) -> Option<Control> {
+    key_named(s).map(Control::Key)
+}
+
+impl Control {
+    /// The name [`parse_control`] reads back, if the control has one
+    pub fn name(self) -> Option<String> {
+        match self {
+            Control::Key(key) => key_name(key).map(str::to_string),
+        }
+    }
+    /// Every control held in `input`
+    pub fn all_down(input: &Input) -> impl Iterator<Item = Control> + '_ {
+        input.keys_down().iter().map(|k| Control::Key(*k))
+    }
+}
+
+/// One player's controls: which keys trigger which actions.
+/// An action can have any number of controls, and a control can
+/// trigger more than one action.
+#[derive(Clone, Debug)]
+pub struct Bindings<A> {
+    bound: Vec<(A, Control)>,
+}
+
+impl<A> Default for Bindings<A> {
+    fn default() -> Self {
+        Self { bound: vec![] }
+    }
+}
+
+impl<A: Copy + PartialEq> Bindings<A> {
+    pub fn bind(&mut self, action: A, control: Control) {
+        if !self.bound.contains(&(action, control)) {
+            self.bound.push((action, control));
+        }
+    }
+    /// Removes every control from `action`
+    pub fn clear(&mut self, action: A) {
+        self.bound.retain(|(a, _)| *a != action);
+    }
+    /// Makes `control` the only way to trigger `action`
+    pub fn rebind(&mut self, action: A, control: Control) {
+        self.clear(action);
+        self.bind(action, control);
+    }
+    pub fn controls(&self, action: A) -> impl Iterator<Item = Control> + '_ {
+        self.bound
+            .iter()
+            .filter(move |(a, _)| *a == action)
+            .map(|(_, c)| *c)
+    }
+    /// Is any of the action's controls held?
+    pub fn is_down(&self, input: &Input, action: A) -> bool {
+        self.controls(action).any(|c| c.is_down(input))
+    }
+    /// Did the action just start this frame?  Pressing a second control
+    /// for an action that's already held doesn't count.
+    pub fn is_pressed(&self, input: &Input, action: A) -> bool {
+        self.is_down(input, action) && !self.controls(action).any(|c| c.was_down(input))
+    }
+    /// Did the action just stop this frame, with all its controls let go?
+    pub fn is_released(&self, input: &Input, action: A) -> bool {
+        !self.is_down(input, action) && self.controls(action).any(|c| c.was_down(input))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindingParseError {
+    Missing { line: usize, what: &'static str },
+    BadPlayer { line: usize, text: String },
+    UnknownAction { line: usize, name: String },
+    UnknownControl { line: usize, name: String },
+}
+
+impl std::fmt::Display for BindingParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { line, what } => write!(f, "line {line}: no {what}"),
+            Self::BadPlayer { line, text } => {
+                write!(f, "line {line}: players are numbered from 1, not {text:?}")
+            }
+            Self::UnknownAction { line, name } => {
+                write!(f, "line {line}: unknown action {name:?}")
+            }
+            Self::UnknownControl { line, name } => {
+                write!(f, "line {line}: unknown key {name:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BindingParseError {}
+
+/// Every player's [`Bindings`], with players numbered from 0.
+#[derive(Clone, Debug)]
+pub struct ActionMap<A> {
+    players: Vec<Bindings<A>>,
+    /// What players the file doesn't bind get
+    unbound: Bindings<A>,
+}
+
+/*
+Control files bind one action per line for one player.  Players count
+from 1, and a line can list several controls.  Lines for the same
+action add to each other.  Blank lines and lines starting with # are
+ignored:
+
+PLAYER ACTION CONTROL [CONTROL ...]
+1 left ArrowLeft KeyA
+*/
+impl<A: Copy + PartialEq + FromStr> ActionMap<A> {
+    pub fn parse(s: &str) -> Result<Self, BindingParseError> {
+        let mut players: Vec<Bindings<A>> = vec![];
+        for (line_no, line) in s.lines().enumerate() {
+            let line_no = line_no + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let player = fields.next().unwrap();
+            let player = match player.parse::<usize>() {
+                Ok(p) if p > 0 => p - 1,
+                _ => {
+                    return Err(BindingParseError::BadPlayer {
+                        line: line_no,
+                        text: player.to_string(),
+                    })
+                }
+            };
+            let name = fields.next().ok_or(BindingParseError::Missing {
+                line: line_no,
+                what: "action",
+            })?;
+            let action = A::from_str(name).map_err(|_| BindingParseError::UnknownAction {
+                line: line_no,
+                name: name.to_string(),
+            })?;
+            if players.len() <= player {
+                players.resize_with(player + 1, Bindings::default);
+            }
+            let mut any = false;
+            for name in fields {
+                let control = parse_control(name).ok_or(BindingParseError::UnknownControl {
+                    line: line_no,
+                    name: name.to_string(),
+                })?;
+                players[player].bind(action, control);
+                any = true;
+            }
+            if !any {
+                return Err(BindingParseError::Missing {
+                    line: line_no,
+                    what: "key",
+                });
+            }
+        }
+        Ok(Self {
+            players,
+            unbound: Bindings::default(),
+        })
+    }
+    /// Loads the control file asset `name`.  Panics if it's missing or
+    /// malformed, like [`crate::campaign::Campaign::load`].
+    pub fn load(cache: &Assets, name: &str) -> Self {
+        Self::parse(
+            &cache
+                .load::<String>(name)
+                .unwrap_or_else(|_| panic!("Couldn't access {name}.txt"))
+                .read(),
+        )
+        .unwrap_or_else(|e| panic!("Couldn't parse {name}.txt: {e}"))
+    }
+}
+
+impl<A: Copy + PartialEq> ActionMap<A> {
+    /// How many players have bindings
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+    /// A player's bindings, which are empty for players with none
+    pub fn player(&self, player: usize) -> &Bindings<A> {
+        self.players.get(player).unwrap_or(&self.unbound)
+    }
+    /// A player's bindings to change, adding the player if they're new
+    pub fn player_mut(&mut self, player: usize) -> &mut Bindings<A> {
+        if self.players.len() <= player {
+            self.players.resize_with(player + 1, Bindings::default);
+        }
+        &mut self.players[player]
+    }
+    /// Did any player just start `action`?  Handy for menus anyone can use.
+    pub fn any_pressed(&self, input: &Input, action: A) -> bool {
+        self.players.iter().any(|b| b.is_pressed(input, action))
+    }
+}
+
+/// Direction presses waiting for the next movement tick, for games
+/// like Snake that only move every few frames.  Presses are recorded
+/// every frame and handed out one per tick, so quick turns like Up then
+/// Left within one step both happen.  A press that would reverse the
+/// direction in effect by then, or repeat it, is dropped.
+#[derive(Clone, Debug)]
+pub struct TurnBuffer {
+    queued: VecDeque<Dir>,
+    capacity: usize,
+}
+
+impl Default for TurnBuffer {
+    /// Remembers up to three turns ahead
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl TurnBuffer {
+    /// A buffer holding at most `capacity` turns; presses past that are
+    /// dropped until a tick makes room.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queued: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    /// The direction in effect once every queued turn has been taken
+    pub fn last(&self, heading: Dir) -> Dir {
+        self.queued.back().copied().unwrap_or(heading)
+    }
+    /// Queues a turn for something currently moving `heading`.  Returns
+    /// whether it was kept.
+    pub fn push(&mut self, heading: Dir, dir: Dir) -> bool {
+        let last = self.last(heading);
+        if self.queued.len() >= self.capacity || dir == last || dir == last.opposite() {
+            return false;
+        }
+        self.queued.push_back(dir);
+        true
+    }
+    /// Queues every turn whose action was pressed this frame, in `turns`
+    /// order.
+    pub fn record<A: Copy + PartialEq>(
+        &mut self,
+        heading: Dir,
+        bindings: &Bindings<A>,
+        input: &Input,
+        turns: &[(A, Dir)],
+    ) {
+        for (action, dir) in turns {
+            if bindings.is_pressed(input, *action) {
+                self.push(heading, *dir);
+            }
+        }
+    }
+    /// Takes the next turn on a movement tick, giving the direction to
+    /// move in: the turn, or `heading` if nothing was queued.
+    pub fn next_turn(&mut self, heading: Dir) -> Dir {
+        self.queued.pop_front().unwrap_or(heading)
+    }
+    pub fn clear(&mut self) {
+        self.queued.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Action {
+        Left,
+        Fire,
+    }
+    impl FromStr for Action {
+        type Err = ();
+        fn from_str(s: &str) -> Result<Self, ()> {
+            match s {
+                "left" => Ok(Action::Left),
+                "fire" => Ok(Action::Fire),
+                _ => Err(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let map = ActionMap::<Action>::parse(
+            "# controls\n1 left ArrowLeft KeyA\n1 fire Space\n\n2 left KeyA\n1 fire Enter\n",
+        )
+        .unwrap();
+        assert_eq!(map.len(), 2);
+        let p1: Vec<Control> = map.player(0).controls(Action::Left).collect();
+        assert_eq!(p1, [Control::Key(Key::ArrowLeft), Control::Key(Key::KeyA)]);
+        assert_eq!(map.player(0).controls(Action::Fire).count(), 2);
+        assert_eq!(map.player(1).controls(Action::Fire).count(), 0);
+        // players the file leaves out, or a file with only comments, bind nothing
+        assert_eq!(map.player(5).controls(Action::Left).count(), 0);
+        let mut empty = ActionMap::<Action>::parse("# no one\n").unwrap();
+        assert!(empty.is_empty());
+        assert!(!empty.player(0).is_down(&Input::default(), Action::Fire));
+        empty
+            .player_mut(1)
+            .bind(Action::Fire, Control::Key(Key::Space));
+        assert_eq!(empty.len(), 2);
+        assert_eq!(
+            ActionMap::<Action>::parse("0 left KeyA").unwrap_err(),
+            BindingParseError::BadPlayer {
+                line: 1,
+                text: "0".to_string()
+            }
+        );
+        assert_eq!(
+            ActionMap::<Action>::parse("1 jump KeyA").unwrap_err(),
+            BindingParseError::UnknownAction {
+                line: 1,
+                name: "jump".to_string()
+            }
+        );
+        assert_eq!(
+            ActionMap::<Action>::parse("1 left Kayak").unwrap_err(),
+            BindingParseError::UnknownControl {
+                line: 1,
+                name: "Kayak".to_string()
+            }
+        );
+        assert!(matches!(
+            ActionMap::<Action>::parse("\n1 left"),
+            Err(BindingParseError::Missing { line: 2, .. })
+        ));
+        for control in [Control::Key(Key::Numpad5), Control::Key(Key::BracketLeft)] {
+            assert_eq!(parse_control(&control.name().unwrap()), Some(control));
+        }
+        assert_eq!(parse_control("pad1:South"), None);
+    }
+    #[test]
+    fn test_pressed_and_released() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::Fire, Control::Key(Key::Space));
+        bindings.bind(Action::Fire, Control::Key(Key::Enter));
+        let mut input = Input::default();
+        input.press(Key::Enter);
+        assert!(bindings.is_pressed(&input, Action::Fire));
+        assert!(!bindings.is_down(&input, Action::Left));
+        input.next_frame();
+        // a second control for the same held action isn't a new press
+        input.press(Key::Space);
+        assert!(bindings.is_down(&input, Action::Fire));
+        assert!(!bindings.is_pressed(&input, Action::Fire));
+        input.next_frame();
+        input.release(Key::Enter);
+        assert!(!bindings.is_released(&input, Action::Fire));
+        input.next_frame();
+        input.release(Key::Space);
+        assert!(bindings.is_released(&input, Action::Fire));
+        // rebinding replaces the old controls
+        bindings.rebind(Action::Fire, Control::Key(Key::KeyF));
+        input.next_frame();
+        input.press(Key::Space);
+        assert!(!bindings.is_down(&input, Action::Fire));
+        input.press(Key::KeyF);
+        assert!(bindings.is_pressed(&input, Action::Fire));
+    }
+    #[test]
+    fn test_turn_buffer() {
+        let mut turns = TurnBuffer::new(2);
+        // up then left between ticks are both kept, in order
+        assert!(turns.push(Dir::Right, Dir::Up));
+        assert!(turns.push(Dir::Right, Dir::Left));
+        // reversing what will be in effect, or going past capacity, isn't
+        assert_eq!(turns.last(Dir::Right), Dir::Left);
+        assert!(!turns.push(Dir::Right, Dir::Down));
+        assert_eq!(turns.next_turn(Dir::Right), Dir::Up);
+        assert!(!turns.push(Dir::Up, Dir::Right));
+        assert!(!turns.push(Dir::Up, Dir::Left));
+        assert_eq!(turns.next_turn(Dir::Up), Dir::Left);
+        assert_eq!(turns.next_turn(Dir::Left), Dir::Left);
+        assert!(turns.is_empty());
+
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::Left, Control::Key(Key::ArrowLeft));
+        bindings.bind(Action::Fire, Control::Key(Key::ArrowUp));
+        let map = [(Action::Left, Dir::Left), (Action::Fire, Dir::Up)];
+        let mut input = Input::default();
+        input.press(Key::ArrowUp);
+        input.press(Key::ArrowLeft);
+        turns.record(Dir::Right, &bindings, &input, &map);
+        assert_eq!(turns.len(), 1);
+        // holding the keys doesn't queue them again
+        input.next_frame();
+        turns.record(Dir::Right, &bindings, &input, &map);
+        assert_eq!(turns.next_turn(Dir::Right), Dir::Up);
+        assert!(turns.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::grid::{Coord, Grid};
+use crate::Dir;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Every cell reachable from `start` by 4-way steps through cells
+/// where `open` is true, nearest first.  `start` itself is always
+/// included, even if it isn't open (it's usually where a player is).
+pub fn flood_fill<T>(grid: &Grid<T>, start: Coord, open: impl Fn(&T) -> bool) -> Vec<Coord> {
+    let mut seen = vec![false; grid.width() * grid.height()];
+    let Some(start_idx) = grid.coord_to_index(start) else {
+        return vec![];
+    };
+    seen[start_idx] = true;
+    let mut found = vec![start];
+    let mut next = 0;
+    while let Some(&(x, y)) = found.get(next) {
+        next += 1;
+        for n in grid.neighbors_4(x, y) {
+            let idx = grid.coord_to_index(n).unwrap();
+            if !seen[idx] && open(&grid[idx]) {
+                seen[idx] = true;
+                found.push(n);
+            }
+        }
+    }
+    found
+}
+
+/// How many cells [`flood_fill`] would find: a quick measure of how
+/// much room there is to move around in from `start`.
+pub fn reachable_area<T>(grid: &Grid<T>, start: Coord, open: impl Fn(&T) -> bool) -> usize {
+    flood_fill(grid, start, open).len()
+}
+
+/// Follows `came_from` back from `goal` to build a start-to-goal path.
+fn unwind<T>(grid: &Grid<T>, came_from: &[Option<usize>], start: Coord, goal: Coord) -> Vec<Coord> {
+    let mut path = vec![goal];
+    let mut at = goal;
+    while at != start {
+        let idx = grid.coord_to_index(at).unwrap();
+        at = grid.index_to_coord(came_from[idx].unwrap()).unwrap();
+        path.push(at);
+    }
+    path.reverse();
+    path
+}
+
+/// The shortest 4-way path from `start` to `goal` through open cells,
+/// found breadth-first.  The path includes both ends, and `start`
+/// needn't be open.  `None` if `goal` can't be reached.
+pub fn bfs_path<T>(
+    grid: &Grid<T>,
+    start: Coord,
+    goal: Coord,
+    open: impl Fn(&T) -> bool,
+) -> Option<Vec<Coord>> {
+    let start_idx = grid.coord_to_index(start)?;
+    grid.coord_to_index(goal)?;
+    let mut came_from = vec![None; grid.width() * grid.height()];
+    let mut seen = vec![false; came_from.len()];
+    seen[start_idx] = true;
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y)) = queue.pop_front() {
+        if (x, y) == goal {
+            return Some(unwind(grid, &came_from, start, goal));
+        }
+        for n in grid.neighbors_4(x, y) {
+            let idx = grid.coord_to_index(n).unwrap();
+            if !seen[idx] && open(&grid[idx]) {
+                seen[idx] = true;
+                came_from[idx] = grid.xy_to_index(x, y);
+                queue.push_back(n);
+            }
+        }
+    }
+    None
+}
+
+/// Like [`bfs_path`], but searching towards `goal` first using the
+/// Manhattan distance, so it usually looks at far fewer cells on open
+/// maps.  Paths are just as short.
+pub fn astar_path<T>(
+    grid: &Grid<T>,
+    start: Coord,
+    goal: Coord,
+    open: impl Fn(&T) -> bool,
+) -> Option<Vec<Coord>> {
+    let start_idx = grid.coord_to_index(start)?;
+    grid.coord_to_index(goal)?;
+    let estimate = |(x, y): Coord| x.abs_diff(goal.0) + y.abs_diff(goal.1);
+    let mut came_from = vec![None; grid.width() * grid.height()];
+    let mut cost = vec![usize::MAX; came_from.len()];
+    cost[start_idx] = 0;
+    // ties go to the cell found first, so searches are repeatable
+    let mut frontier = BinaryHeap::from([Reverse((estimate(start), 0, start_idx))]);
+    let mut order = 0;
+    while let Some(Reverse((_, _, idx))) = frontier.pop() {
+        let (x, y) = grid.index_to_coord(idx).unwrap();
+        if (x, y) == goal {
+            return Some(unwind(grid, &came_from, start, goal));
+        }
+        for n in grid.neighbors_4(x, y) {
+            let n_idx = grid.coord_to_index(n).unwrap();
+            let n_cost = cost[idx] + 1;
+            if n_cost < cost[n_idx] && open(&grid[n_idx]) {
+                cost[n_idx] = n_cost;
+                came_from[n_idx] = Some(idx);
+                order += 1;
+                frontier.push(Reverse((n_cost + estimate(n), order, n_idx)));
+            }
+        }
+    }
+    None
+}
+
+/// Which way to step from `from` to reach the neighbouring cell `to`,
+/// using the grid's rows-down convention (see [`Dir::step`]).
+pub fn dir_between(from: Coord, to: Coord) -> Option<Dir> {
+    [Dir::Up, Dir::Right, Dir::Down, Dir::Left]
+        .into_iter()
+        .find(|dir| dir.step(from) == Some(to))
+}
+
+/// The way to go from `from`, other than straight back against
+/// `heading`, that leads to the most open cells.  Going straight wins
+/// ties.  `None` if every way is blocked.
+pub fn roomiest_dir<T>(
+    grid: &Grid<T>,
+    from: Coord,
+    heading: Dir,
+    open: impl Fn(&T) -> bool,
+) -> Option<Dir> {
+    let mut best: Option<(Dir, usize)> = None;
+    for dir in [heading, Dir::Up, Dir::Right, Dir::Down, Dir::Left] {
+        if dir == heading.opposite() || best.is_some_and(|(d, _)| d == dir) {
+            continue;
+        }
+        let Some(next) = dir.step(from) else {
+            continue;
+        };
+        if !grid.get(next.0, next.1).is_some_and(&open) {
+            continue;
+        }
+        let area = reachable_area(grid, next, &open);
+        if best.is_none_or(|(_, most)| area > most) {
+            best = Some((dir, area));
+        }
+    }
+    best.map(|(dir, _)| dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `#` is a wall, anything else is open
+    fn grid(rows: &[&str]) -> Grid<bool> {
+        let cells: Vec<bool> = rows
+            .iter()
+            .flat_map(|r| r.chars().map(|c| c != '#'))
+            .collect();
+        Grid::new(rows[0].len(), rows.len(), cells)
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let g = grid(&["..#..", "..#..", "###.."]);
+        let left = flood_fill(&g, (0, 0), |open| *open);
+        assert_eq!(left, [(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(reachable_area(&g, (4, 2), |open| *open), 6);
+        // a blocked start still counts, and can be left on either side
+        assert_eq!(reachable_area(&g, (2, 0), |open| *open), 11);
+        assert!(flood_fill(&g, (9, 9), |open| *open).is_empty());
+    }
+    #[test]
+    fn test_paths() {
+        let g = grid(&[".....", ".###.", "...#.", "#.#..", "....."]);
+        let bfs = bfs_path(&g, (0, 2), (4, 2), |open| *open).unwrap();
+        let astar = astar_path(&g, (0, 2), (4, 2), |open| *open).unwrap();
+        assert_eq!((bfs[0], *bfs.last().unwrap()), ((0, 2), (4, 2)));
+        assert_eq!(bfs.len(), astar.len());
+        assert_eq!(bfs.len(), 9);
+        // every step is to a neighbour through an open cell
+        for path in [&bfs, &astar] {
+            for pair in path.windows(2) {
+                assert!(dir_between(pair[0], pair[1]).is_some(), "{pair:?}");
+                assert!(g[pair[1]]);
+            }
+        }
+        assert_eq!(
+            bfs_path(&g, (1, 1), (1, 1), |open| *open),
+            Some(vec![(1, 1)])
+        );
+        let walled = grid(&["..#..", "..#.."]);
+        assert_eq!(bfs_path(&walled, (0, 0), (4, 0), |open| *open), None);
+        assert_eq!(astar_path(&walled, (0, 0), (4, 0), |open| *open), None);
+        assert_eq!(dir_between((2, 2), (2, 1)), Some(Dir::Up));
+        assert_eq!(dir_between((2, 2), (4, 2)), None);
+    }
+    #[test]
+    fn test_roomiest_dir() {
+        // going right leads into a dead end, up has the open room
+        let g = grid(&["......", "......", "#.####", "##.#.#", "######"]);
+        assert_eq!(
+            roomiest_dir(&g, (1, 3), Dir::Right, |open| *open),
+            Some(Dir::Up)
+        );
+        // straight ahead wins ties
+        let open = grid(&["...", "...", "..."]);
+        assert_eq!(
+            roomiest_dir(&open, (1, 1), Dir::Left, |open| *open),
+            Some(Dir::Left)
+        );
+        // and there's no turning back
+        let corridor = grid(&["###", "...", "###"]);
+        assert_eq!(
+            roomiest_dir(&corridor, (2, 1), Dir::Right, |open| *open),
+            None
+        );
+    }
+}
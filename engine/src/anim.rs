@@ -0,0 +1,377 @@
+use crate::app::Assets;
+use frenderer::sprites::SheetRegion;
+use std::str::FromStr;
+
+/// What an animation does after its last frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Starts over from the first frame
+    Loop,
+    /// Stays on the last frame
+    Once,
+    /// Plays backwards to the first frame, then forwards again
+    PingPong,
+}
+
+impl FromStr for Mode {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "loop" => Ok(Mode::Loop),
+            "once" => Ok(Mode::Once),
+            "pingpong" => Ok(Mode::PingPong),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A region of the sprite sheet and how many seconds it shows for
+pub type Frame = (SheetRegion, f32);
+
+/// A list of frames, each shown for some number of seconds
+#[derive(Clone, Debug)]
+pub struct Animation {
+    frames: Vec<Frame>,
+    mode: Mode,
+}
+
+impl Animation {
+    /// Panics if there are no frames
+    pub fn new(mode: Mode, frames: Vec<Frame>) -> Self {
+        assert!(!frames.is_empty(), "animations need at least one frame");
+        Self { frames, mode }
+    }
+    /// A single frame that never changes
+    pub fn still(region: SheetRegion) -> Self {
+        Self::new(Mode::Once, vec![(region, 1.0)])
+    }
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+    /// Seconds to play through every frame once
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|(_, secs)| secs).sum()
+    }
+    /// Which frame is showing `time` seconds in
+    pub fn frame_index(&self, time: f32) -> usize {
+        let last = self.frames.len() - 1;
+        let duration = self.duration();
+        if last == 0 || duration <= 0.0 {
+            return 0;
+        }
+        // ping-pong plays the middle frames again on the way back
+        let back = self.frames[1..last]
+            .iter()
+            .map(|(_, secs)| secs)
+            .sum::<f32>();
+        let time = match self.mode {
+            Mode::Once => time.min(duration),
+            Mode::Loop => time.rem_euclid(duration),
+            Mode::PingPong => time.rem_euclid(duration + back),
+        };
+        let mut start = 0.0;
+        for (i, (_, secs)) in self.frames.iter().enumerate() {
+            start += secs;
+            if time < start {
+                return i;
+            }
+        }
+        // past the end: on the way back in ping-pong, else the last frame
+        if self.mode == Mode::PingPong {
+            for i in (1..last).rev() {
+                start += self.frames[i].1;
+                if time < start {
+                    return i;
+                }
+            }
+        }
+        last
+    }
+    pub fn frame(&self, time: f32) -> SheetRegion {
+        self.frames[self.frame_index(time)].0
+    }
+    /// Whether a [`Mode::Once`] animation has reached its end.  Others
+    /// never finish.
+    pub fn is_finished(&self, time: f32) -> bool {
+        self.mode == Mode::Once && time >= self.duration()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnimParseError {
+    /// A frame line before any anim line
+    NoAnimation {
+        line: usize,
+    },
+    BadLine {
+        line: usize,
+        text: String,
+    },
+    UnknownMode {
+        line: usize,
+        name: String,
+    },
+    Duplicate {
+        line: usize,
+        name: String,
+    },
+    NoFrames {
+        name: String,
+    },
+}
+
+impl std::fmt::Display for AnimParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAnimation { line } => write!(f, "line {line}: frame isn't in an anim"),
+            Self::BadLine { line, text } => write!(f, "line {line}: can't read {text:?}"),
+            Self::UnknownMode { line, name } => write!(
+                f,
+                "line {line}: unknown mode {name:?}, expected loop, once or pingpong"
+            ),
+            Self::Duplicate { line, name } => {
+                write!(f, "line {line}: there's already an anim called {name:?}")
+            }
+            Self::NoFrames { name } => write!(f, "anim {name:?} has no frames"),
+        }
+    }
+}
+
+impl std::error::Error for AnimParseError {}
+
+/// A game's animations, by name
+#[derive(Clone, Debug, Default)]
+pub struct AnimSet {
+    anims: Vec<(String, Animation)>,
+}
+
+/*
+Animation files have an anim line naming each animation and its mode
+(loop, once or pingpong), followed by its frames: where the frame is on
+the sprite sheet and how many seconds it shows for.  Blank lines and
+lines starting with # are ignored:
+
+anim NAME MODE
+frame X Y W H SECONDS
+
+anim gem loop
+frame 1 47 8 8 0.8
+frame 10 47 8 8 0.1
+*/
+impl AnimSet {
+    pub fn parse(s: &str) -> Result<Self, AnimParseError> {
+        let mut anims: Vec<(String, Vec<Frame>, Mode)> = vec![];
+        for (line_no, line) in s.lines().enumerate() {
+            let line_no = line_no + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = || AnimParseError::BadLine {
+                line: line_no,
+                text: line.to_string(),
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                ["anim", name, mode] => {
+                    let mode = mode.parse().map_err(|_| AnimParseError::UnknownMode {
+                        line: line_no,
+                        name: mode.to_string(),
+                    })?;
+                    if anims.iter().any(|(n, _, _)| n == name) {
+                        return Err(AnimParseError::Duplicate {
+                            line: line_no,
+                            name: name.to_string(),
+                        });
+                    }
+                    anims.push((name.to_string(), vec![], mode));
+                }
+                ["frame", x, y, w, h, secs] => {
+                    let (_, frames, _) = anims
+                        .last_mut()
+                        .ok_or(AnimParseError::NoAnimation { line: line_no })?;
+                    let region = SheetRegion::rect(
+                        x.parse().map_err(|_| bad())?,
+                        y.parse().map_err(|_| bad())?,
+                        w.parse().map_err(|_| bad())?,
+                        h.parse().map_err(|_| bad())?,
+                    );
+                    let secs: f32 = secs.parse().map_err(|_| bad())?;
+                    if secs.is_nan() || secs < 0.0 {
+                        return Err(bad());
+                    }
+                    frames.push((region, secs));
+                }
+                _ => return Err(bad()),
+            }
+        }
+        let anims = anims
+            .into_iter()
+            .map(|(name, frames, mode)| {
+                if frames.is_empty() {
+                    Err(AnimParseError::NoFrames { name })
+                } else {
+                    Ok((name, Animation::new(mode, frames)))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { anims })
+    }
+    /// Loads the animation file asset `name`.  Panics if it's missing
+    /// or malformed, like [`crate::campaign::Campaign::load`].
+    pub fn load(cache: &Assets, name: &str) -> Self {
+        Self::parse(
+            &cache
+                .load::<String>(name)
+                .unwrap_or_else(|_| panic!("Couldn't access {name}.txt"))
+                .read(),
+        )
+        .unwrap_or_else(|e| panic!("Couldn't parse {name}.txt: {e}"))
+    }
+    /// Adds or replaces the animation called `name`
+    pub fn insert(&mut self, name: &str, anim: Animation) {
+        match self.anims.iter_mut().find(|(n, _)| n == name) {
+            Some((_, a)) => *a = anim,
+            None => self.anims.push((name.to_string(), anim)),
+        }
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.anims.iter().any(|(n, _)| n == name)
+    }
+    /// Panics if there's no such animation
+    pub fn get(&self, name: &str) -> &Animation {
+        self.anims
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, a)| a)
+            .unwrap_or_else(|| panic!("No animation named {name:?}"))
+    }
+}
+
+/// Where one entity is in its current animation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnimPlayer {
+    name: String,
+    time: f32,
+}
+
+impl AnimPlayer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            time: 0.0,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Seconds since the animation started
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+    /// Switches to the animation called `name`, starting it from the
+    /// beginning.  Does nothing if it's already playing, so this can be
+    /// called every frame.
+    pub fn play(&mut self, name: &str) {
+        if self.name != name {
+            *self = Self::new(name);
+        }
+    }
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+    /// Moves the animation along by `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+    pub fn frame(&self, anims: &AnimSet) -> SheetRegion {
+        anims.get(&self.name).frame(self.time)
+    }
+    pub fn is_finished(&self, anims: &AnimSet) -> bool {
+        anims.get(&self.name).is_finished(self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames 0, 1 and 2 at x = 0, 10 and 20, shown for 1, 2 and 1 seconds
+    fn anim(mode: Mode) -> Animation {
+        Animation::new(
+            mode,
+            vec![
+                (SheetRegion::rect(0, 0, 8, 8), 1.0),
+                (SheetRegion::rect(10, 0, 8, 8), 2.0),
+                (SheetRegion::rect(20, 0, 8, 8), 1.0),
+            ],
+        )
+    }
+    fn frames(anim: &Animation, times: &[f32]) -> Vec<usize> {
+        times.iter().map(|t| anim.frame_index(*t)).collect()
+    }
+
+    #[test]
+    fn test_modes() {
+        let times = [0.0, 0.5, 1.0, 2.9, 3.0, 4.0, 4.5, 6.9, 7.0, 8.5];
+        let looped = anim(Mode::Loop);
+        assert_eq!(frames(&looped, &times), [0, 0, 1, 1, 2, 0, 0, 1, 2, 0]);
+        let once = anim(Mode::Once);
+        assert_eq!(frames(&once, &times), [0, 0, 1, 1, 2, 2, 2, 2, 2, 2]);
+        assert!(!once.is_finished(3.9));
+        assert!(once.is_finished(4.0));
+        assert!(!looped.is_finished(100.0));
+        // 0 1 1 2 1 1, then over again
+        let ping = anim(Mode::PingPong);
+        assert_eq!(frames(&ping, &times), [0, 0, 1, 1, 2, 1, 1, 0, 1, 1]);
+        assert_eq!(ping.frame(3.5).x, 20);
+        let still = Animation::still(SheetRegion::rect(5, 5, 8, 8));
+        assert_eq!(still.frame(99.0).x, 5);
+    }
+    #[test]
+    fn test_parse_and_play() {
+        let anims = AnimSet::parse(
+            "# claw\nanim open once\nframe 1 56 8 16 0.1\n\nanim gem pingpong\nframe 1 47 8 8 0.5\nframe 10 47 8 8 0.5\n",
+        )
+        .unwrap();
+        assert_eq!(anims.get("gem").mode(), Mode::PingPong);
+        assert_eq!(anims.get("gem").frame_count(), 2);
+        assert!(!anims.contains("closed"));
+        let mut player = AnimPlayer::new("gem");
+        player.update(0.6);
+        assert_eq!(player.frame(&anims).x, 10);
+        // playing the same animation keeps its place, a new one restarts
+        player.play("gem");
+        assert_eq!(player.time(), 0.6);
+        player.play("open");
+        assert_eq!((player.name(), player.time()), ("open", 0.0));
+        player.update(0.2);
+        assert!(player.is_finished(&anims));
+
+        assert_eq!(
+            AnimSet::parse("frame 1 1 8 8 1").unwrap_err(),
+            AnimParseError::NoAnimation { line: 1 }
+        );
+        assert!(matches!(
+            AnimSet::parse("anim a bounce"),
+            Err(AnimParseError::UnknownMode { line: 1, .. })
+        ));
+        assert!(matches!(
+            AnimSet::parse("anim a loop\nframe 1 1 8 8 1\nanim a once\nframe 1 1 8 8 1"),
+            Err(AnimParseError::Duplicate { line: 3, .. })
+        ));
+        assert!(matches!(
+            AnimSet::parse("anim a loop\nframe 1 1 8 8 soon"),
+            Err(AnimParseError::BadLine { line: 2, .. })
+        ));
+        assert_eq!(
+            AnimSet::parse("anim a loop\n").unwrap_err(),
+            AnimParseError::NoFrames {
+                name: "a".to_string()
+            }
+        );
+    }
+}
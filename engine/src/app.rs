@@ -0,0 +1,266 @@
+use crate::input::Input;
+use crate::replay::Replay;
+use crate::save::Storage;
+use frenderer::{
+    clock::Clock,
+    sprites::{SheetRegion, Transform},
+    Immediate,
+};
+
+// On the web there's no file system to read content from, so games
+// embed their content directory into the binary instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub type Source = assets_manager::source::FileSystem;
+#[cfg(target_arch = "wasm32")]
+pub type Source = assets_manager::source::Embedded<'static>;
+
+pub type Assets = assets_manager::AssetCache<Source>;
+
+/// The random number generator handed to every game.  Seeding two of
+/// them the same way gives the same numbers on every platform, which
+/// replays rely on.
+pub type GameRng = rand_chacha::ChaCha8Rng;
+
+/// A [`GameRng`] started from `seed`
+pub fn seeded_rng(seed: u64) -> GameRng {
+    rand::SeedableRng::seed_from_u64(seed)
+}
+
+/// Where a game's assets live.  Use the [`crate::content`] macro to
+/// pick the right one for the current target.
+pub enum Content {
+    Dir(&'static str),
+    #[cfg(target_arch = "wasm32")]
+    Embedded(assets_manager::source::RawEmbedded<'static>),
+}
+
+/// Expands to the [`app::Content`](crate::app::Content) for a game's
+/// `content` directory: a path on native targets, or the directory
+/// embedded into the binary on wasm.
+#[macro_export]
+macro_rules! content {
+    () => {{
+        #[cfg(not(target_arch = "wasm32"))]
+        let content = $crate::app::Content::Dir("content");
+        #[cfg(target_arch = "wasm32")]
+        let content = $crate::app::Content::Embedded(::assets_manager::source::embed!("content"));
+        content
+    }};
+}
+
+impl Content {
+    pub(crate) fn into_assets(self) -> Assets {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Content::Dir(path) => assets_manager::AssetCache::with_source(
+                Source::new(path).expect("Couldn't load resources"),
+            ),
+            #[cfg(target_arch = "wasm32")]
+            Content::Dir(_) => panic!("Can't read a content directory on the web, embed it"),
+            #[cfg(target_arch = "wasm32")]
+            Content::Embedded(raw) => assets_manager::AssetCache::with_source(Source::from(raw)),
+        }
+    }
+}
+
+pub struct AppConfig {
+    pub title: &'static str,
+    /// Logical size of the window
+    pub window_size: (f32, f32),
+    /// Size of the render target, i.e. the game's "virtual screen"
+    pub render_size: (u32, u32),
+    /// Length of one simulation step in seconds
+    pub dt: f32,
+    /// Most simulation steps we'll run for a single rendered frame
+    /// before giving up on catching up (death spiral prevention)
+    pub max_frames_per_tick: usize,
+    pub content: Content,
+    /// Seed for the game's RNG; a random one if `None`
+    pub seed: Option<u64>,
+    /// Save a [`Replay`] of the run here when the window closes.  The
+    /// run starts without the player's saves and doesn't touch them.
+    pub record: Option<std::path::PathBuf>,
+    /// Play this [`Replay`] back instead of reading live input, until
+    /// it runs out.  Like recording, this leaves saves alone.
+    pub playback: Option<std::path::PathBuf>,
+}
+
+impl AppConfig {
+    /// Fills in `seed`, `record` and `playback` from the command line:
+    /// `--seed N`, `--record FILE` and `--replay FILE`.
+    pub fn with_args(mut self) -> Self {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match (arg.as_str(), args.next()) {
+                ("--seed", Some(seed)) => {
+                    self.seed = Some(seed.parse().expect("--seed needs a number"))
+                }
+                ("--record", Some(path)) => self.record = Some(path.into()),
+                ("--replay", Some(path)) => self.playback = Some(path.into()),
+                (arg, _) => panic!("Unknown argument {arg}; try --seed, --record or --replay"),
+            }
+        }
+        self
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            title: "game",
+            window_size: (1024.0, 768.0),
+            render_size: (320, 240),
+            dt: 1.0 / 60.0,
+            max_frames_per_tick: 4,
+            content: Content::Dir("content"),
+            seed: None,
+            record: None,
+            playback: None,
+        }
+    }
+}
+
+/// Anything sprites can be drawn into: the real renderer, or a
+/// [`crate::headless::RecordingSink`] when there's no GPU around.
+pub trait SpriteSink {
+    fn draw_sprite(&mut self, group: usize, transform: Transform, sheet_region: SheetRegion);
+    /// Gets a block of `howmany` sprites to draw into.
+    fn draw_sprites(
+        &mut self,
+        group: usize,
+        howmany: usize,
+    ) -> (&mut [Transform], &mut [SheetRegion]);
+}
+
+impl SpriteSink for Immediate {
+    fn draw_sprite(&mut self, group: usize, transform: Transform, sheet_region: SheetRegion) {
+        Immediate::draw_sprite(self, group, transform, sheet_region)
+    }
+    fn draw_sprites(
+        &mut self,
+        group: usize,
+        howmany: usize,
+    ) -> (&mut [Transform], &mut [SheetRegion]) {
+        Immediate::draw_sprites(self, group, howmany)
+    }
+}
+
+/// A game that [`run`] can drive, simulated at a fixed timestep and
+/// rendered once per frame.  Game state is built in `new`, which must
+/// not need a GPU so that [`crate::headless::Headless`] can run the
+/// game too; textures and sprite groups are set up in `init_graphics`.
+/// Any randomness should come from the `rng` passed to `new`, so that
+/// runs can be replayed, and anything kept between runs goes in
+/// `storage`.
+pub trait Game: Sized + 'static {
+    fn new(cache: &Assets, rng: GameRng, storage: Storage) -> Self;
+    fn init_graphics(&mut self, renderer: &mut Immediate, cache: &Assets);
+    fn simulate(&mut self, input: &Input, dt: f32);
+    fn render(&mut self, frend: &mut impl SpriteSink);
+    /// Called when the window is closing, e.g. to save a snapshot
+    fn quit(&mut self) {}
+}
+
+/// Opens a window and runs `G` in it until the window is closed.
+pub fn run<G: Game>(config: AppConfig) {
+    let AppConfig {
+        title,
+        window_size,
+        render_size,
+        dt,
+        max_frames_per_tick,
+        content,
+        seed,
+        record,
+        playback,
+    } = config;
+    let cache = content.into_assets();
+    let playback = playback.map(|path| {
+        Replay::load(&path).unwrap_or_else(|e| panic!("Couldn't load {}: {e}", path.display()))
+    });
+    let seed = playback
+        .as_ref()
+        .map(|r| r.seed)
+        .or(seed)
+        .unwrap_or_else(rand::random);
+    // a replay plays at the timestep it was recorded with
+    let dt = playback.as_ref().map_or(dt, |r| r.dt);
+    let mut recording = Replay::new(seed, dt);
+    let mut tick = 0;
+    // a saved game would change how a replay plays out, so replays
+    // start from nothing and don't save over the player's games
+    let replaying = record.is_some() || playback.is_some();
+
+    let drv = frenderer::Driver::new(
+        winit::window::WindowBuilder::new()
+            .with_title(title)
+            .with_inner_size(winit::dpi::LogicalSize::new(window_size.0, window_size.1)),
+        Some(render_size),
+    );
+
+    let mut input = Input::default();
+    let mut clock = Clock::new(dt, 0.0002, max_frames_per_tick);
+    drv.run_event_loop::<(), _>(
+        move |window, frend| {
+            let mut frend = Immediate::new(frend);
+            let storage = if replaying {
+                Storage::in_memory()
+            } else {
+                Storage::in_data_dir(title)
+            };
+            let mut game = G::new(&cache, seeded_rng(seed), storage);
+            game.init_graphics(&mut frend, &cache);
+            (window, game, frend)
+        },
+        move |event, target, (window, ref mut game, ref mut frend)| {
+            use winit::event::{Event, WindowEvent};
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    game.quit();
+                    if let Some(path) = &record {
+                        if let Err(e) = recording.save(path) {
+                            eprintln!("Couldn't save replay to {}: {e}", path.display());
+                        }
+                    }
+                    target.exit();
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => {
+                    if !frend.gpu().is_web() {
+                        frend.resize_surface(size.width, size.height);
+                    }
+                    window.request_redraw();
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::RedrawRequested,
+                    ..
+                } => {
+                    // The clock snaps elapsed time to common refresh
+                    // rates and drops time beyond max_frames_per_tick
+                    // steps, so one slow frame can't snowball.
+                    for _ in 0..clock.tick() {
+                        if let Some(replay) = playback.as_ref().filter(|r| tick < r.ticks()) {
+                            replay.apply(tick, &mut input);
+                        }
+                        recording.record(&input);
+                        game.simulate(&input, dt);
+                        input.next_frame();
+                        tick += 1;
+                    }
+                    game.render(frend);
+                    frend.render();
+                    window.request_redraw();
+                }
+                event => {
+                    input.process_input_event(&event);
+                }
+            }
+        },
+    )
+    .expect("event loop error");
+}
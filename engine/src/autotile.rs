@@ -0,0 +1,283 @@
+use crate::ai::dir_between;
+use crate::grid::{Coord, Grid};
+use crate::Dir;
+use std::str::FromStr;
+
+/// The bit for `dir` in a connection mask, which has one bit set for
+/// each side of a cell that joins up with its neighbour
+pub fn side_bit(dir: Dir) -> u8 {
+    match dir {
+        Dir::Up => 1,
+        Dir::Right => 2,
+        Dir::Down => 4,
+        Dir::Left => 8,
+    }
+}
+
+/// The sides set in a connection mask
+pub fn sides(mask: u8) -> impl Iterator<Item = Dir> {
+    [Dir::Up, Dir::Right, Dir::Down, Dir::Left]
+        .into_iter()
+        .filter(move |dir| mask & side_bit(*dir) != 0)
+}
+
+/// The shape of a cell by how many sides it connects on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Piece {
+    /// Connects on no side
+    Single,
+    /// One side: the head or tail of a trail
+    End,
+    /// Two opposite sides
+    Straight,
+    /// Two neighbouring sides
+    Corner,
+    /// Three sides
+    Tee,
+    /// All four sides
+    Cross,
+}
+
+/// A piece and how many quarter turns counterclockwise to draw it with.
+/// Art for each piece connects on its left side: an `End` on the left,
+/// a `Straight` on the left and right, a `Corner` on the left and
+/// bottom, and a `Tee` on every side but the right.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub piece: Piece,
+    pub turns: u8,
+}
+
+impl Tile {
+    const fn new(piece: Piece, turns: u8) -> Self {
+        Self { piece, turns }
+    }
+    /// The rotation in radians for a sprite `Transform`
+    pub fn rot(self) -> f32 {
+        self.turns as f32 * std::f32::consts::FRAC_PI_2
+    }
+}
+
+/// The tile for each connection mask, see [`side_bit`]
+pub const TILES_4: [Tile; 16] = {
+    use Piece::*;
+    [
+        Tile::new(Single, 0),   // none
+        Tile::new(End, 3),      // up
+        Tile::new(End, 2),      // right
+        Tile::new(Corner, 2),   // up right
+        Tile::new(End, 1),      // down
+        Tile::new(Straight, 1), // up down
+        Tile::new(Corner, 1),   // right down
+        Tile::new(Tee, 2),      // up right down
+        Tile::new(End, 0),      // left
+        Tile::new(Corner, 3),   // up left
+        Tile::new(Straight, 0), // right left
+        Tile::new(Tee, 3),      // up right left
+        Tile::new(Corner, 0),   // down left
+        Tile::new(Tee, 0),      // up down left
+        Tile::new(Tee, 1),      // right down left
+        Tile::new(Cross, 0),    // all
+    ]
+};
+
+/// The tile for a connection mask
+pub fn tile_4(mask: u8) -> Tile {
+    TILES_4[(mask & 15) as usize]
+}
+
+/// The connection mask for each cell of a trail, such as a snake's body
+/// or a light cycle's wall, in order.  Each cell joins the cells just
+/// before and after it if they're next to it on the grid.
+pub fn trail_masks(cells: impl IntoIterator<Item = Coord>) -> Vec<u8> {
+    let cells: Vec<Coord> = cells.into_iter().collect();
+    (0..cells.len())
+        .map(|i| {
+            let before = i.checked_sub(1).map(|j| cells[j]);
+            let after = cells.get(i + 1).copied();
+            before
+                .into_iter()
+                .chain(after)
+                .filter_map(|other| dir_between(cells[i], other))
+                .fold(0, |mask, dir| mask | side_bit(dir))
+        })
+        .collect()
+}
+
+/// The bit for the neighbour at offset `(dx, dy)` in an 8-neighbour
+/// mask, going clockwise from up (`y` counts down the grid):
+///
+/// ```text
+/// 128   1   2
+///  64   .   4
+///  32  16   8
+/// ```
+pub fn neighbour_bit(dx: isize, dy: isize) -> u8 {
+    match (dx, dy) {
+        (0, -1) => 1,
+        (1, -1) => 2,
+        (1, 0) => 4,
+        (1, 1) => 8,
+        (0, 1) => 16,
+        (-1, 1) => 32,
+        (-1, 0) => 64,
+        (-1, -1) => 128,
+        _ => 0,
+    }
+}
+
+/// Which of the eight cells around `(x, y)` match, as an 8-neighbour
+/// mask.  Cells off the edge of the grid count as matching, so walls
+/// painted along a border carry on past it.
+pub fn neighbour_mask<T>(grid: &Grid<T>, x: usize, y: usize, matches: impl Fn(&T) -> bool) -> u8 {
+    grid.neighbors_8(x, y)
+        .filter(|&(nx, ny)| !matches(&grid[(nx, ny)]))
+        .fold(u8::MAX, |mask, (nx, ny)| {
+            mask & !neighbour_bit(nx as isize - x as isize, ny as isize - y as isize)
+        })
+}
+
+/// How an autotile group picks one of its tiles from a cell's
+/// 8-neighbour mask (see [`neighbour_bit`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileSet {
+    /// 16 tiles by which sides match, numbered like [`side_bit`]
+    Edge16,
+    /// 47 tiles by which sides and corners match, where a corner only
+    /// counts when both sides next to it match too.  Tiles are numbered
+    /// in order of their reduced masks.
+    Blob47,
+    /// 16 tiles by which corners are surrounded on all three sides:
+    /// top left 1, top right 2, bottom right 4, bottom left 8
+    Marching16,
+}
+
+impl TileSet {
+    /// How many tiles the set needs
+    pub fn tile_count(self) -> usize {
+        match self {
+            Self::Edge16 | Self::Marching16 => 16,
+            Self::Blob47 => 47,
+        }
+    }
+    /// Which tile of the set fits a cell with 8-neighbour mask `mask`
+    pub fn index(self, mask: u8) -> usize {
+        match self {
+            Self::Edge16 => [
+                (1, Dir::Up),
+                (4, Dir::Right),
+                (16, Dir::Down),
+                (64, Dir::Left),
+            ]
+            .into_iter()
+            .filter(|(bit, _)| mask & bit != 0)
+            .fold(0, |m, (_, dir)| m | side_bit(dir)) as usize,
+            Self::Blob47 => {
+                let mask = blob_reduce(mask);
+                (0..mask).filter(|&m| blob_reduce(m) == m).count()
+            }
+            Self::Marching16 => [
+                (1 | 64 | 128, 1),
+                (1 | 2 | 4, 2),
+                (4 | 8 | 16, 4),
+                (16 | 32 | 64, 8),
+            ]
+            .into_iter()
+            .filter(|(corner, _)| mask & corner == *corner)
+            .fold(0, |m, (_, bit)| m | bit),
+        }
+    }
+}
+
+/// Drops the corners of an 8-neighbour mask that don't have both of
+/// their sides set
+fn blob_reduce(mask: u8) -> u8 {
+    [(2, 1 | 4), (8, 4 | 16), (32, 16 | 64), (128, 64 | 1)]
+        .into_iter()
+        .filter(|(_, sides)| mask & sides != *sides)
+        .fold(mask, |mask, (corner, _)| mask & !corner)
+}
+
+impl FromStr for TileSet {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "edge16" => Ok(Self::Edge16),
+            "blob47" => Ok(Self::Blob47),
+            "marching16" => Ok(Self::Marching16),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turns a mask a quarter turn counterclockwise on screen
+    fn turn(mask: u8) -> u8 {
+        sides(mask)
+            .map(|dir| match dir {
+                Dir::Left => Dir::Down,
+                Dir::Down => Dir::Right,
+                Dir::Right => Dir::Up,
+                Dir::Up => Dir::Left,
+            })
+            .fold(0, |m, dir| m | side_bit(dir))
+    }
+
+    #[test]
+    fn test_tiles_turn_from_their_art() {
+        let base = |piece| match piece {
+            Piece::Single => 0,
+            Piece::End => 8,
+            Piece::Straight => 10,
+            Piece::Corner => 12,
+            Piece::Tee => 13,
+            Piece::Cross => 15,
+        };
+        for mask in 0..16 {
+            let tile = tile_4(mask);
+            let turned = (0..tile.turns).fold(base(tile.piece), |m, _| turn(m));
+            assert_eq!(turned, mask, "{tile:?}");
+        }
+    }
+
+    #[test]
+    fn test_trail_masks() {
+        // head at the top, down two cells, then right, then a gap
+        let trail = [(1, 1), (1, 2), (1, 3), (2, 3), (5, 5)];
+        let tiles: Vec<Tile> = trail_masks(trail).into_iter().map(tile_4).collect();
+        assert_eq!(
+            tiles,
+            [
+                Tile::new(Piece::End, 1),
+                Tile::new(Piece::Straight, 1),
+                Tile::new(Piece::Corner, 2),
+                Tile::new(Piece::End, 0),
+                Tile::new(Piece::Single, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tile_sets() {
+        let blobs: std::collections::HashSet<usize> =
+            (0..=255).map(|m| TileSet::Blob47.index(m)).collect();
+        assert_eq!(blobs.len(), 47);
+        assert_eq!(blobs.iter().max(), Some(&46));
+        // a wall along the top of a room, open below
+        let grid = Grid::new(3, 2, [true, true, true, false, false, false]);
+        let mask = neighbour_mask(&grid, 1, 0, |wall| *wall);
+        assert_eq!(mask, 1 | 2 | 4 | 64 | 128);
+        assert_eq!(TileSet::Edge16.index(mask), 1 | 2 | 8);
+        assert_eq!(TileSet::Marching16.index(mask), 1 | 2);
+        // the lone corner below-right doesn't count without both sides
+        assert_eq!(
+            TileSet::Blob47.index(1 | 4 | 8),
+            TileSet::Blob47.index(1 | 4)
+        );
+        assert_eq!(TileSet::Blob47.index(0), 0);
+        assert_eq!(TileSet::Blob47.index(u8::MAX), 46);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::app::Assets;
+use crate::level::Level;
+use std::str::FromStr;
+
+/// What a player has to do to finish a level.  Levels without a score
+/// or apple goal are won by surviving until the time limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Goals {
+    pub target_score: Option<usize>,
+    pub apples: Option<usize>,
+    /// In seconds
+    pub time_limit: Option<f32>,
+}
+
+/// How the player is doing on the current level, for checking against
+/// its [`Goals`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub score: usize,
+    pub apples: usize,
+    /// Seconds spent on the level so far
+    pub elapsed: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Playing,
+    Won,
+    Lost,
+}
+
+impl Goals {
+    pub fn outcome(&self, progress: &Progress) -> Outcome {
+        let has_target = self.target_score.is_some() || self.apples.is_some();
+        let met = self.target_score.is_none_or(|s| progress.score >= s)
+            && self.apples.is_none_or(|a| progress.apples >= a);
+        let out_of_time = self.time_limit.is_some_and(|t| progress.elapsed >= t);
+        match (has_target, met, out_of_time) {
+            (true, true, _) => Outcome::Won,
+            (false, _, true) => Outcome::Won,
+            (true, false, true) => Outcome::Lost,
+            _ => Outcome::Playing,
+        }
+    }
+    /// Seconds left before the time limit, if there is one
+    pub fn time_left(&self, progress: &Progress) -> Option<f32> {
+        self.time_limit.map(|t| (t - progress.elapsed).max(0.0))
+    }
+}
+
+/// One line of a campaign manifest: the level asset to load and its goals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CampaignEntry {
+    pub name: String,
+    pub goals: Goals,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CampaignParseError {
+    Missing {
+        line: usize,
+        what: &'static str,
+    },
+    BadNumber {
+        line: usize,
+        what: &'static str,
+        text: String,
+    },
+    UnknownGoal {
+        line: usize,
+        goal: String,
+    },
+    /// The same level or goal listed twice
+    Duplicate {
+        line: usize,
+        what: &'static str,
+        text: String,
+    },
+    Empty,
+}
+
+impl std::fmt::Display for CampaignParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { line, what } => write!(f, "line {line}: no {what}"),
+            Self::BadNumber { line, what, text } => {
+                write!(f, "line {line}: couldn't parse {what} from {text:?}")
+            }
+            Self::UnknownGoal { line, goal } => write!(
+                f,
+                "line {line}: goals are score, apples or time, not {goal:?}"
+            ),
+            Self::Duplicate { line, what, text } => {
+                write!(f, "line {line}: duplicate {what} {text:?}")
+            }
+            Self::Empty => write!(f, "campaign has no levels"),
+        }
+    }
+}
+
+impl std::error::Error for CampaignParseError {}
+
+/*
+Campaign manifests list one level per line, in the order they're played.
+Blank lines and lines starting with # are ignored:
+
+LEVEL-ASSET [score N] [apples N] [time SECONDS]
+LEVEL-ASSET [score N] [apples N] [time SECONDS]
+*/
+pub fn parse_manifest(s: &str) -> Result<Vec<CampaignEntry>, CampaignParseError> {
+    let mut entries: Vec<CampaignEntry> = vec![];
+    for (line_no, line) in s.lines().enumerate() {
+        let line_no = line_no + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let name = fields.next().unwrap().to_string();
+        if entries.iter().any(|e| e.name == name) {
+            return Err(CampaignParseError::Duplicate {
+                line: line_no,
+                what: "level",
+                text: name,
+            });
+        }
+        let mut goals = Goals::default();
+        while let Some(goal) = fields.next() {
+            let what = match goal {
+                "score" => "target score",
+                "apples" => "apple count",
+                "time" => "time limit",
+                _ => {
+                    return Err(CampaignParseError::UnknownGoal {
+                        line: line_no,
+                        goal: goal.to_string(),
+                    })
+                }
+            };
+            let text = fields.next().ok_or(CampaignParseError::Missing {
+                line: line_no,
+                what,
+            })?;
+            let bad_number = || CampaignParseError::BadNumber {
+                line: line_no,
+                what,
+                text: text.to_string(),
+            };
+            let duplicate = match goal {
+                "score" => goals
+                    .target_score
+                    .replace(text.parse().map_err(|_| bad_number())?)
+                    .is_some(),
+                "apples" => goals
+                    .apples
+                    .replace(text.parse().map_err(|_| bad_number())?)
+                    .is_some(),
+                _ => {
+                    let secs: f32 = text.parse().map_err(|_| bad_number())?;
+                    if secs.is_nan() || secs <= 0.0 {
+                        return Err(bad_number());
+                    }
+                    goals.time_limit.replace(secs).is_some()
+                }
+            };
+            if duplicate {
+                return Err(CampaignParseError::Duplicate {
+                    line: line_no,
+                    what: "goal",
+                    text: goal.to_string(),
+                });
+            }
+        }
+        entries.push(CampaignEntry { name, goals });
+    }
+    if entries.is_empty() {
+        return Err(CampaignParseError::Empty);
+    }
+    Ok(entries)
+}
+
+/// An ordered list of levels with their goals, and which one is being
+/// played.  Every level is loaded up front, so moving between them
+/// doesn't need the asset cache.
+pub struct Campaign<E> {
+    entries: Vec<CampaignEntry>,
+    levels: Vec<Level<E>>,
+    current: usize,
+}
+
+impl<E: FromStr> Campaign<E> {
+    /// Loads the manifest asset `manifest` and every level it lists.
+    /// Panics if any of them is missing or malformed, since a game
+    /// can't do anything sensible without its levels.
+    pub fn load(cache: &Assets, manifest: &str) -> Self {
+        let entries = parse_manifest(
+            &cache
+                .load::<String>(manifest)
+                .unwrap_or_else(|_| panic!("Couldn't access {manifest}.txt"))
+                .read(),
+        )
+        .unwrap_or_else(|e| panic!("Couldn't parse {manifest}.txt: {e}"));
+        let levels = entries
+            .iter()
+            .map(|entry| Level::load(cache, &entry.name))
+            .collect();
+        Self::new(entries, levels)
+    }
+}
+
+impl<E> Campaign<E> {
+    /// A campaign playing `levels` in order, starting with the first.
+    /// `entries` gives each level's name and goals.
+    pub fn new(entries: Vec<CampaignEntry>, levels: Vec<Level<E>>) -> Self {
+        assert!(!levels.is_empty(), "Campaign has no levels");
+        assert_eq!(
+            entries.len(),
+            levels.len(),
+            "Campaign needs exactly one entry per level"
+        );
+        Self {
+            entries,
+            levels,
+            current: 0,
+        }
+    }
+    pub fn level(&self) -> &Level<E> {
+        &self.levels[self.current]
+    }
+    pub fn goals(&self) -> &Goals {
+        &self.entries[self.current].goals
+    }
+    pub fn name(&self) -> &str {
+        &self.entries[self.current].name
+    }
+    /// Position of the current level in the campaign, from 0
+    pub fn index(&self) -> usize {
+        self.current
+    }
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+    pub fn is_last(&self) -> bool {
+        self.current + 1 == self.levels.len()
+    }
+    /// Moves on to the next level.  Returns false, staying put, if this
+    /// was the last one.
+    pub fn advance(&mut self) -> bool {
+        if self.is_last() {
+            return false;
+        }
+        self.current += 1;
+        true
+    }
+    /// Goes back to the first level.
+    pub fn restart(&mut self) {
+        self.current = 0;
+    }
+    /// Moves to the level named `name`.  Returns false, staying put, if
+    /// the campaign doesn't have one.
+    pub fn jump_to(&mut self, name: &str) -> bool {
+        match self.entries.iter().position(|e| e.name == name) {
+            Some(idx) => {
+                self.current = idx;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let entries = parse_manifest(
+            "# the campaign\nlevel score 650 time 30\n\nlevel2 apples 10\nbonus time 12.5\n",
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, "level");
+        assert_eq!(
+            entries[0].goals,
+            Goals {
+                target_score: Some(650),
+                apples: None,
+                time_limit: Some(30.0),
+            }
+        );
+        assert_eq!(entries[1].goals.apples, Some(10));
+        assert_eq!(entries[2].goals.time_limit, Some(12.5));
+        assert_eq!(
+            parse_manifest("# nothing\n"),
+            Err(CampaignParseError::Empty)
+        );
+        assert_eq!(
+            parse_manifest("a\nb lives 3\n"),
+            Err(CampaignParseError::UnknownGoal {
+                line: 2,
+                goal: "lives".to_string()
+            })
+        );
+        assert!(matches!(
+            parse_manifest("a score\n"),
+            Err(CampaignParseError::Missing { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_manifest("a time -1\n"),
+            Err(CampaignParseError::BadNumber { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_manifest("a\na\n"),
+            Err(CampaignParseError::Duplicate { line: 2, .. })
+        ));
+    }
+    #[test]
+    fn test_outcomes() {
+        let score = Goals {
+            target_score: Some(100),
+            time_limit: Some(30.0),
+            ..Goals::default()
+        };
+        let progress = |score, elapsed| Progress {
+            score,
+            apples: 0,
+            elapsed,
+        };
+        assert_eq!(score.outcome(&progress(50, 10.0)), Outcome::Playing);
+        assert_eq!(score.outcome(&progress(100, 10.0)), Outcome::Won);
+        assert_eq!(score.outcome(&progress(100, 31.0)), Outcome::Won);
+        assert_eq!(score.outcome(&progress(50, 30.0)), Outcome::Lost);
+        assert_eq!(score.time_left(&progress(0, 12.0)), Some(18.0));
+        assert_eq!(score.time_left(&progress(0, 40.0)), Some(0.0));
+        // surviving the clock wins when there's nothing else to do
+        let survive = Goals {
+            time_limit: Some(5.0),
+            ..Goals::default()
+        };
+        assert_eq!(survive.outcome(&progress(0, 4.0)), Outcome::Playing);
+        assert_eq!(survive.outcome(&progress(0, 5.0)), Outcome::Won);
+        // and a level without goals goes on forever
+        assert_eq!(
+            Goals::default().outcome(&progress(0, 1000.0)),
+            Outcome::Playing
+        );
+    }
+    #[test]
+    fn test_progression() {
+        let level = |name: &str| {
+            Level::<u8>::parse(&format!("{name} 1 1\n====\n. o 0 0 4 4\n====\n.\n")).unwrap()
+        };
+        let entries = parse_manifest("one\ntwo apples 3\nthree\n").unwrap();
+        let mut campaign = Campaign::new(entries, vec![level("a"), level("b"), level("c")]);
+        assert_eq!(campaign.len(), 3);
+        assert_eq!((campaign.index(), campaign.name()), (0, "one"));
+        assert!(campaign.advance());
+        assert_eq!(campaign.level().name(), "b");
+        assert_eq!(campaign.goals().apples, Some(3));
+        assert!(campaign.advance());
+        assert!(campaign.is_last());
+        assert!(!campaign.advance());
+        assert_eq!(campaign.name(), "three");
+        assert!(campaign.jump_to("two"));
+        assert_eq!(campaign.index(), 1);
+        assert!(!campaign.jump_to("four"));
+        assert_eq!(campaign.index(), 1);
+        campaign.restart();
+        assert_eq!(campaign.name(), "one");
+    }
+}
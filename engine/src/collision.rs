@@ -0,0 +1,291 @@
+use crate::level::Level;
+use crate::{Rect, Vec2};
+use std::collections::HashMap;
+
+/// An overlap between rect `index_a` of one list and rect `index_b`
+/// of another.  For tile contacts `index_b` is the tile's index in
+/// the level grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub rect_a: Rect,
+    pub index_a: usize,
+    pub rect_b: Rect,
+    pub index_b: usize,
+    pub overlap: Vec2,
+}
+
+/// Gathers every overlapping pair of an `a_rects` rect and a `b_rects` rect.
+pub fn gather_contacts(a_rects: &[Rect], b_rects: &[Rect], contacts: &mut Vec<Contact>) {
+    for (i, a_rect) in a_rects.iter().enumerate() {
+        for (j, b_rect) in b_rects.iter().enumerate() {
+            if let Some(overlap) = a_rect.overlap(*b_rect) {
+                contacts.push(Contact {
+                    index_a: i,
+                    rect_a: *a_rect,
+                    index_b: j,
+                    rect_b: *b_rect,
+                    overlap,
+                })
+            }
+        }
+    }
+}
+
+/// Gathers contacts between `rects` and the solid tiles of `level`.
+pub fn gather_tile_contacts<E>(rects: &[Rect], level: &Level<E>, contacts: &mut Vec<Contact>) {
+    let tile_sz = level.tile_size() as f32;
+    for (i, rect) in rects.iter().enumerate() {
+        for (tr, _) in level.tiles_within(*rect).filter(|(_tr, td)| td.solid) {
+            if let Some(overlap) = rect.overlap(tr) {
+                // the cell whose bottom left corner is at the tile rect's,
+                // undoing grid_to_world (rows count down from the top)
+                let col = (tr.x / tile_sz).round() as usize;
+                let Some(row) = level
+                    .height()
+                    .checked_sub((tr.y / tile_sz).round() as usize + 1)
+                else {
+                    continue;
+                };
+                if level.get_tile((col, row)).is_none() {
+                    continue;
+                }
+                let index_b = row * level.width() + col;
+                contacts.push(Contact {
+                    index_a: i,
+                    rect_a: *rect,
+                    index_b,
+                    rect_b: tr,
+                    overlap,
+                })
+            }
+        }
+    }
+}
+
+/// Sorts contacts from the biggest overlap to the smallest, since
+/// resolving the deepest contacts first often resolves the others too.
+pub fn sort_contacts(contacts: &mut [Contact]) {
+    contacts.sort_by(|a, b| {
+        b.overlap
+            .length_squared()
+            .partial_cmp(&a.overlap.length_squared())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// The smallest displacement that moves `a` out of `b`, along
+/// whichever axis overlaps less.  Zero if they don't overlap.
+pub fn compute_displacement(a: Rect, b: Rect) -> Vec2 {
+    let Some(mut overlap) = a.overlap(b) else {
+        return Vec2::ZERO;
+    };
+    if overlap.y < overlap.x {
+        overlap.x = 0.0;
+    } else {
+        overlap.y = 0.0;
+    }
+    if a.x < b.x {
+        overlap.x *= -1.0;
+    }
+    if a.y < b.y {
+        overlap.y *= -1.0;
+    }
+    overlap
+}
+
+/// A uniform grid of buckets for broad-phase collision: each rect is
+/// stored in every cell it touches, so a query only has to check the
+/// rects near it rather than all of them.
+pub struct SpatialHash {
+    cell_sz: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_sz: f32) -> Self {
+        assert!(
+            cell_sz > 0.0,
+            "Spatial hash cells must have a positive size"
+        );
+        Self {
+            cell_sz,
+            cells: HashMap::new(),
+        }
+    }
+    /// Builds a hash holding every rect of `rects`, keyed by its index.
+    pub fn with_rects(cell_sz: f32, rects: &[Rect]) -> Self {
+        let mut hash = Self::new(cell_sz);
+        for (i, rect) in rects.iter().enumerate() {
+            hash.insert(i, *rect);
+        }
+        hash
+    }
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(Vec::clear);
+    }
+    pub fn insert(&mut self, index: usize, rect: Rect) {
+        for cell in self.cells_within(rect) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+    /// Appends the indices of every rect that might overlap `rect` to
+    /// `out`, in increasing order and without duplicates.
+    pub fn query(&self, rect: Rect, out: &mut Vec<usize>) {
+        let start = out.len();
+        for cell in self.cells_within(rect) {
+            if let Some(indices) = self.cells.get(&cell) {
+                out.extend_from_slice(indices);
+            }
+        }
+        out[start..].sort_unstable();
+        let mut found: Vec<usize> = out.drain(start..).collect();
+        found.dedup();
+        out.extend(found);
+    }
+    fn cells_within(&self, rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        // Rects that just touch count as overlapping, so include the far edges.
+        let l = (rect.x / self.cell_sz).floor() as i32;
+        let b = (rect.y / self.cell_sz).floor() as i32;
+        let r = ((rect.x + rect.w as f32) / self.cell_sz).floor() as i32;
+        let t = ((rect.y + rect.h as f32) / self.cell_sz).floor() as i32;
+        (b..=t).flat_map(move |y| (l..=r).map(move |x| (x, y)))
+    }
+}
+
+/// Like [`gather_contacts`], but only tests each `a_rects` rect
+/// against the `b_rects` rects that `b_hash` puts near it.  `b_hash`
+/// must have been built from `b_rects`.
+pub fn gather_contacts_hashed(
+    a_rects: &[Rect],
+    b_rects: &[Rect],
+    b_hash: &SpatialHash,
+    contacts: &mut Vec<Contact>,
+) {
+    let mut nearby = vec![];
+    for (i, a_rect) in a_rects.iter().enumerate() {
+        nearby.clear();
+        b_hash.query(*a_rect, &mut nearby);
+        for &j in nearby.iter() {
+            let b_rect = b_rects[j];
+            if let Some(overlap) = a_rect.overlap(b_rect) {
+                contacts.push(Contact {
+                    index_a: i,
+                    rect_a: *a_rect,
+                    index_b: j,
+                    rect_b: b_rect,
+                    overlap,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, w: u16, h: u16) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    #[test]
+    fn test_overlap_edges() {
+        let a = rect(0.0, 0.0, 4, 4);
+        // touching edges and corners count, with zero overlap
+        assert_eq!(a.overlap(rect(4.0, 0.0, 4, 4)), Some(Vec2::new(0.0, 4.0)));
+        assert_eq!(a.overlap(rect(4.0, 4.0, 4, 4)), Some(Vec2::ZERO));
+        assert_eq!(a.overlap(rect(4.1, 0.0, 4, 4)), None);
+        // containment and identical rects overlap by the smaller rect
+        assert_eq!(a.overlap(rect(1.0, 1.0, 2, 2)), Some(Vec2::new(2.0, 2.0)));
+        assert_eq!(a.overlap(a), Some(Vec2::new(4.0, 4.0)));
+        // empty rects still touch things
+        assert_eq!(a.overlap(rect(2.0, 2.0, 0, 0)), Some(Vec2::ZERO));
+    }
+    #[test]
+    fn test_displacement() {
+        let a = rect(0.0, 0.0, 4, 4);
+        assert_eq!(
+            compute_displacement(a, rect(3.0, 1.0, 4, 4)),
+            Vec2::new(-1.0, 0.0)
+        );
+        assert_eq!(
+            compute_displacement(a, rect(1.0, -2.0, 4, 4)),
+            Vec2::new(0.0, 2.0)
+        );
+        assert_eq!(compute_displacement(a, rect(4.0, 0.0, 4, 4)), Vec2::ZERO);
+        assert_eq!(compute_displacement(a, rect(5.0, 0.0, 4, 4)), Vec2::ZERO);
+        // equal overlaps push along x
+        assert_eq!(
+            compute_displacement(a, rect(-3.0, 3.0, 4, 4)),
+            Vec2::new(1.0, 0.0)
+        );
+    }
+    #[test]
+    fn test_gather_and_sort() {
+        let a = [rect(0.0, 0.0, 4, 4), rect(20.0, 20.0, 4, 4)];
+        let b = [
+            rect(3.0, 3.0, 4, 4),
+            rect(100.0, 0.0, 4, 4),
+            rect(1.0, 1.0, 4, 4),
+        ];
+        let mut contacts = vec![];
+        gather_contacts(&a, &b, &mut contacts);
+        assert_eq!(contacts.len(), 2);
+        sort_contacts(&mut contacts);
+        assert_eq!(contacts[0].index_b, 2);
+        assert_eq!(contacts[0].overlap, Vec2::new(3.0, 3.0));
+        assert_eq!(contacts[1].index_b, 0);
+    }
+    #[test]
+    fn test_spatial_hash_matches_brute_force() {
+        let a: Vec<Rect> = (0..20)
+            .map(|i| rect((i * 7 % 50) as f32, (i * 13 % 50) as f32, 6, 5))
+            .collect();
+        let b: Vec<Rect> = (0..30)
+            .map(|i| rect((i * 11 % 60) as f32 - 5.0, (i * 3 % 60) as f32, 4, 8))
+            .collect();
+        let mut brute = vec![];
+        gather_contacts(&a, &b, &mut brute);
+        for cell_sz in [1.0, 4.0, 8.0, 100.0] {
+            let mut hashed = vec![];
+            gather_contacts_hashed(&a, &b, &SpatialHash::with_rects(cell_sz, &b), &mut hashed);
+            assert_eq!(brute, hashed, "cell size {cell_sz}");
+        }
+    }
+    #[test]
+    fn test_tile_contacts() {
+        #[derive(Debug)]
+        struct NoEntities;
+        impl std::str::FromStr for NoEntities {
+            type Err = ();
+            fn from_str(_s: &str) -> Result<Self, ()> {
+                Err(())
+            }
+        }
+        let level = Level::<NoEntities>::parse(
+            "test 3 2\n====\n. o 0 0 4 4\nx s 4 0 4 4\n====\nx . .\n. . x\n",
+        )
+        .unwrap();
+        let mut contacts = vec![];
+        // overlaps the open tile at (1, 1) and the solid one at (2, 1)
+        gather_tile_contacts(&[rect(6.0, 1.0, 4, 2)], &level, &mut contacts);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].rect_b, rect(8.0, 0.0, 4, 4));
+        assert_eq!(contacts[0].index_b, 5);
+        assert_eq!(contacts[0].overlap, Vec2::new(2.0, 2.0));
+        // one-unit tiles, touching the solid tiles in both corners
+        let level = Level::<NoEntities>::parse(
+            "test 3 2 1\n====\n. o 0 0 4 4\nx s 4 0 4 4\n====\nx . .\n. . x\n",
+        )
+        .unwrap();
+        contacts.clear();
+        gather_tile_contacts(&[rect(0.0, 0.0, 3, 2)], &level, &mut contacts);
+        let mut tiles: Vec<(Rect, usize)> =
+            contacts.iter().map(|c| (c.rect_b, c.index_b)).collect();
+        tiles.sort_by_key(|(_, index)| *index);
+        assert_eq!(
+            tiles,
+            [(rect(0.0, 1.0, 1, 1), 0), (rect(2.0, 0.0, 1, 1), 5)]
+        );
+    }
+}
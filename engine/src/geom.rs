@@ -0,0 +1,251 @@
+use frenderer::sprites::Transform;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    /// Unit vector pointing `angle` radians counterclockwise from +x
+    pub fn from_angle(angle: f32) -> Self {
+        Self {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+    /// Angle in radians counterclockwise from +x, in `-PI..=PI`
+    pub fn angle(self) -> f32 {
+        self.y.atan2(self.x)
+    }
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+    /// The z component of the 3D cross product, positive when `other`
+    /// is counterclockwise from `self`
+    pub fn cross(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+    pub fn distance(self, other: Vec2) -> f32 {
+        (other - self).length()
+    }
+    /// Unit vector in the same direction, or zero for a zero vector
+    pub fn normalize(self) -> Vec2 {
+        let len = self.length();
+        if len == 0.0 {
+            Vec2::ZERO
+        } else {
+            self / len
+        }
+    }
+    /// Interpolates linearly from `self` (at `t == 0`) to `other` (at `t == 1`)
+    pub fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        self + (other - self) * t
+    }
+    /// Rotates counterclockwise by `angle` radians
+    pub fn rotate(self, angle: f32) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2 {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
+    pub fn approx_eq(self, other: Vec2, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon && (self.y - other.y).abs() <= epsilon
+    }
+    /// A sprite transform centered on this point
+    pub fn to_transform(self, w: u16, h: u16, rot: f32) -> Transform {
+        Transform {
+            x: self.x,
+            y: self.y,
+            w,
+            h,
+            rot,
+        }
+    }
+}
+
+impl std::ops::Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::Output {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+impl std::ops::Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::Output {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
+impl std::ops::Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self::Output {
+            x: self.x * rhs,
+            y: self.y * rhs,
+        }
+    }
+}
+
+impl std::ops::Mul<Vec2> for f32 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: Vec2) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl std::ops::Div<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Self::Output {
+            x: self.x / rhs,
+            y: self.y / rhs,
+        }
+    }
+}
+
+impl std::ops::Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Self::Output {
+        Self::Output {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Vec2 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for Vec2 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::ops::MulAssign<f32> for Vec2 {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+impl std::ops::DivAssign<f32> for Vec2 {
+    fn div_assign(&mut self, rhs: f32) {
+        *self = *self / rhs;
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
+    }
+}
+
+impl From<Transform> for Vec2 {
+    fn from(trf: Transform) -> Self {
+        Self { x: trf.x, y: trf.y }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const EPS: f32 = 1e-5;
+
+    #[test]
+    fn test_ops() {
+        let a = Vec2::new(1.0, 2.0);
+        let b = Vec2::new(3.0, -1.0);
+        assert_eq!(a + b, Vec2::new(4.0, 1.0));
+        assert_eq!(a - b, Vec2::new(-2.0, 3.0));
+        assert_eq!(a * 2.0, Vec2::new(2.0, 4.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(b / 2.0, Vec2::new(1.5, -0.5));
+        assert_eq!(-a, Vec2::new(-1.0, -2.0));
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+        c *= 4.0;
+        c /= 2.0;
+        assert_eq!(c, b * 2.0);
+    }
+    #[test]
+    fn test_products_and_lengths() {
+        let a = Vec2::new(3.0, 4.0);
+        assert_eq!(a.dot(Vec2::new(2.0, 1.0)), 10.0);
+        assert_eq!(Vec2::new(1.0, 0.0).cross(Vec2::new(0.0, 1.0)), 1.0);
+        assert_eq!(Vec2::new(0.0, 1.0).cross(Vec2::new(1.0, 0.0)), -1.0);
+        assert_eq!(a.length_squared(), 25.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(Vec2::ZERO.distance(a), 5.0);
+        assert_eq!(a.distance(a), 0.0);
+        assert!(a.normalize().approx_eq(Vec2::new(0.6, 0.8), EPS));
+        assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+    }
+    #[test]
+    fn test_angles() {
+        assert!(Vec2::from_angle(0.0).approx_eq(Vec2::new(1.0, 0.0), EPS));
+        assert!(Vec2::from_angle(PI / 2.0).approx_eq(Vec2::new(0.0, 1.0), EPS));
+        assert!((Vec2::new(-1.0, 0.0).angle() - PI).abs() < EPS);
+        assert!((Vec2::new(0.0, -2.0).angle() + PI / 2.0).abs() < EPS);
+        assert!(Vec2::new(2.0, 0.0)
+            .rotate(PI / 2.0)
+            .approx_eq(Vec2::new(0.0, 2.0), EPS));
+        let v = Vec2::new(1.0, 1.0);
+        assert!(v.rotate(1.0).rotate(-1.0).approx_eq(v, EPS));
+        assert!(!v.approx_eq(Vec2::new(1.0, 1.1), EPS));
+    }
+    #[test]
+    fn test_lerp_and_conversions() {
+        let a = Vec2::new(0.0, 10.0);
+        let b = Vec2::new(10.0, 20.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vec2::new(5.0, 15.0));
+        assert_eq!(Vec2::from([1.0, 2.0]), Vec2::new(1.0, 2.0));
+        assert_eq!(<[f32; 2]>::from(Vec2::new(1.0, 2.0)), [1.0, 2.0]);
+        let trf = Vec2::new(3.0, 4.0).to_transform(8, 16, 0.5);
+        assert_eq!(
+            (trf.x, trf.y, trf.w, trf.h, trf.rot),
+            (3.0, 4.0, 8, 16, 0.5)
+        );
+        assert_eq!(Vec2::from(trf), Vec2::new(3.0, 4.0));
+    }
+}
@@ -213,4 +213,4 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+}
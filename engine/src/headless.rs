@@ -0,0 +1,282 @@
+use crate::app::{seeded_rng, Assets, Content, Game, SpriteSink};
+use crate::input::{Input, Key};
+use crate::replay::Replay;
+use crate::save::Storage;
+use frenderer::sprites::{SheetRegion, Transform};
+
+/// A [`SpriteSink`] that just remembers what was drawn into each
+/// sprite group, for checking a game's rendering without a GPU.
+#[derive(Default)]
+pub struct RecordingSink {
+    trfs: Vec<Vec<Transform>>,
+    uvs: Vec<Vec<SheetRegion>>,
+}
+
+impl RecordingSink {
+    pub fn clear(&mut self) {
+        self.trfs.iter_mut().for_each(Vec::clear);
+        self.uvs.iter_mut().for_each(Vec::clear);
+    }
+    pub fn sprite_count(&self, group: usize) -> usize {
+        self.trfs.get(group).map_or(0, Vec::len)
+    }
+    pub fn transforms(&self, group: usize) -> &[Transform] {
+        self.trfs.get(group).map_or(&[], Vec::as_slice)
+    }
+    pub fn sheet_regions(&self, group: usize) -> &[SheetRegion] {
+        self.uvs.get(group).map_or(&[], Vec::as_slice)
+    }
+    fn ensure_group(&mut self, group: usize) {
+        if self.trfs.len() <= group {
+            self.trfs.resize_with(group + 1, Vec::new);
+            self.uvs.resize_with(group + 1, Vec::new);
+        }
+    }
+}
+
+impl SpriteSink for RecordingSink {
+    fn draw_sprite(&mut self, group: usize, transform: Transform, sheet_region: SheetRegion) {
+        self.ensure_group(group);
+        self.trfs[group].push(transform);
+        self.uvs[group].push(sheet_region);
+    }
+    fn draw_sprites(
+        &mut self,
+        group: usize,
+        howmany: usize,
+    ) -> (&mut [Transform], &mut [SheetRegion]) {
+        self.ensure_group(group);
+        let old_count = self.trfs[group].len();
+        self.trfs[group].resize(old_count + howmany, Transform::ZERO);
+        self.uvs[group].resize(old_count + howmany, SheetRegion::ZERO);
+        (
+            &mut self.trfs[group][old_count..],
+            &mut self.uvs[group][old_count..],
+        )
+    }
+}
+
+/// Key presses and releases to apply at particular ticks of a
+/// [`Headless`] run.
+#[derive(Clone, Debug, Default)]
+pub struct InputScript {
+    events: Vec<(usize, Key, bool)>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Press `key` just before tick `tick` is simulated.
+    pub fn press(mut self, tick: usize, key: Key) -> Self {
+        self.events.push((tick, key, true));
+        self
+    }
+    pub fn release(mut self, tick: usize, key: Key) -> Self {
+        self.events.push((tick, key, false));
+        self
+    }
+    /// Press `key` for exactly one tick.
+    pub fn tap(self, tick: usize, key: Key) -> Self {
+        self.press(tick, key).release(tick + 1, key)
+    }
+    fn apply(&self, tick: usize, input: &mut Input) {
+        for (_, key, pressed) in self.events.iter().filter(|(t, _, _)| *t == tick) {
+            if *pressed {
+                input.press(*key);
+            } else {
+                input.release(*key);
+            }
+        }
+    }
+}
+
+/// Runs a [`Game`]'s simulation without a window or GPU, one fixed
+/// timestep at a time.  Ticks are counted from zero when the harness
+/// is created.  Every tick's input is recorded into a [`Replay`].
+pub struct Headless<G: Game> {
+    game: G,
+    input: Input,
+    sink: RecordingSink,
+    tick: usize,
+    dt: f32,
+    replay: Replay,
+}
+
+impl<G: Game> Headless<G> {
+    /// A harness for a game seeded with 0, so tests are repeatable
+    pub fn new(content: Content) -> Self {
+        Self::with_cache(&content.into_assets())
+    }
+    pub fn with_cache(cache: &Assets) -> Self {
+        Self::seeded(cache, 0)
+    }
+    pub fn seeded(cache: &Assets, seed: u64) -> Self {
+        Self::build(cache, seed, Storage::in_memory())
+    }
+    /// A harness whose game starts with the files in `storage`, e.g.
+    /// those another harness's game saved
+    pub fn with_storage(content: Content, storage: Storage) -> Self {
+        Self::build(&content.into_assets(), 0, storage)
+    }
+    fn build(cache: &Assets, seed: u64, storage: Storage) -> Self {
+        let dt = 1.0 / 60.0;
+        Self {
+            game: G::new(cache, seeded_rng(seed), storage),
+            input: Input::default(),
+            sink: RecordingSink::default(),
+            tick: 0,
+            dt,
+            replay: Replay::new(seed, dt),
+        }
+    }
+    /// Plays all of `replay` back through a fresh game
+    pub fn play(content: Content, replay: &Replay) -> Self {
+        Self::play_with_cache(&content.into_assets(), replay)
+    }
+    pub fn play_with_cache(cache: &Assets, replay: &Replay) -> Self {
+        let mut h = Self::seeded(cache, replay.seed).with_dt(replay.dt);
+        for tick in 0..replay.ticks() {
+            replay.apply(tick, &mut h.input);
+            h.step();
+        }
+        h
+    }
+    /// Changes the timestep; do this before simulating anything
+    pub fn with_dt(mut self, dt: f32) -> Self {
+        self.dt = dt;
+        self.replay.dt = dt;
+        self
+    }
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+    pub fn game_mut(&mut self) -> &mut G {
+        &mut self.game
+    }
+    /// The number of ticks simulated so far
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+    /// The input state the next tick will see
+    pub fn input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+    /// Simulate a single tick with the current input state.
+    pub fn step(&mut self) {
+        self.replay.record(&self.input);
+        self.game.simulate(&self.input, self.dt);
+        self.input.next_frame();
+        self.tick += 1;
+    }
+    /// Simulate `ticks` more ticks, applying any of `script`'s events
+    /// that fall within them.
+    pub fn run(&mut self, script: &InputScript, ticks: usize) {
+        for _ in 0..ticks {
+            script.apply(self.tick, &mut self.input);
+            self.step();
+        }
+    }
+    /// Tells the game it's being closed, as the window would
+    pub fn quit(&mut self) {
+        self.game.quit();
+    }
+    /// Everything simulated so far, ready to save or play back
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+    /// Render the game's current state into the recording sink.
+    pub fn render(&mut self) -> &RecordingSink {
+        self.sink.clear();
+        self.game.render(&mut self.sink);
+        &self.sink
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::GameRng;
+    use frenderer::Immediate;
+    use rand::Rng;
+
+    // Moves one unit per tick while the right arrow is held and draws
+    // one sprite per unit.  Space rolls a die.
+    struct Counter {
+        x: usize,
+        presses: usize,
+        rolls: Vec<u8>,
+        rng: GameRng,
+    }
+    impl Game for Counter {
+        fn new(_cache: &Assets, rng: GameRng, _storage: Storage) -> Self {
+            Counter {
+                x: 0,
+                presses: 0,
+                rolls: vec![],
+                rng,
+            }
+        }
+        fn init_graphics(&mut self, _renderer: &mut Immediate, _cache: &Assets) {}
+        fn simulate(&mut self, input: &Input, _dt: f32) {
+            if input.is_key_down(Key::ArrowRight) {
+                self.x += 1;
+            }
+            if input.is_key_pressed(Key::Space) {
+                self.presses += 1;
+                self.rolls.push(self.rng.gen_range(1..=6));
+            }
+        }
+        fn render(&mut self, frend: &mut impl SpriteSink) {
+            let (trfs, _uvs) = frend.draw_sprites(1, self.x);
+            for (i, trf) in trfs.iter_mut().enumerate() {
+                trf.x = i as f32;
+            }
+        }
+    }
+
+    #[test]
+    fn test_scripted_input() {
+        let mut h = Headless::<Counter>::with_cache(&Assets::new(".").unwrap());
+        let script = InputScript::new()
+            .press(2, Key::ArrowRight)
+            .release(5, Key::ArrowRight)
+            .tap(6, Key::Space)
+            .tap(8, Key::Space);
+        h.run(&script, 10);
+        assert_eq!(h.tick(), 10);
+        assert_eq!(h.game().x, 3);
+        assert_eq!(h.game().presses, 2);
+    }
+    #[test]
+    fn test_recording_sink() {
+        let mut h = Headless::<Counter>::with_cache(&Assets::new(".").unwrap());
+        h.input_mut().press(Key::ArrowRight);
+        h.run(&InputScript::new(), 4);
+        let sink = h.render();
+        assert_eq!(sink.sprite_count(0), 0);
+        assert_eq!(sink.sprite_count(1), 4);
+        assert_eq!(sink.transforms(1)[3].x, 3.0);
+        // rendering again starts from a clean slate
+        assert_eq!(h.render().sprite_count(1), 4);
+    }
+    #[test]
+    fn test_replay() {
+        let cache = Assets::new(".").unwrap();
+        let mut h = Headless::<Counter>::seeded(&cache, 7);
+        let mut script = InputScript::new().press(1, Key::ArrowRight);
+        for tick in (0..40).step_by(3) {
+            script = script.tap(tick, Key::Space);
+        }
+        h.run(&script, 40);
+        let replay = Replay::parse(&h.replay().to_string()).unwrap();
+        let played = Headless::<Counter>::play_with_cache(&cache, &replay);
+        assert_eq!(played.tick(), 40);
+        assert_eq!(played.game().x, h.game().x);
+        assert_eq!(played.game().rolls, h.game().rolls);
+        // another seed rolls differently
+        let mut other = Headless::<Counter>::seeded(&cache, 8);
+        other.run(&script, 40);
+        assert_ne!(other.game().rolls, h.game().rolls);
+    }
+}
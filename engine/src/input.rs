@@ -0,0 +1,87 @@
+pub use frenderer::input::Key;
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::keyboard::PhysicalKey;
+
+/// Current and previous keyboard state, like `frenderer::input::Input`
+/// but with [`Input::press`] and [`Input::release`] so that tests and
+/// replays can drive it without a window.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    now_keys: Vec<Key>,
+    prev_keys: Vec<Key>,
+}
+
+impl Input {
+    /// Process a [`winit`] event and update the current keys.
+    pub fn process_input_event<T>(&mut self, ev: &Event<T>) {
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            physical_key: PhysicalKey::Code(key),
+                            state,
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } = ev
+        {
+            match state {
+                ElementState::Pressed => self.press(*key),
+                ElementState::Released => self.release(*key),
+            }
+        }
+    }
+    pub fn press(&mut self, key: Key) {
+        if !self.now_keys.contains(&key) {
+            self.now_keys.push(key);
+        }
+    }
+    pub fn release(&mut self, key: Key) {
+        if let Some(idx) = self.now_keys.iter().position(|k| *k == key) {
+            self.now_keys.swap_remove(idx);
+        }
+    }
+    /// Is this key currently down?
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.now_keys.contains(&key)
+    }
+    /// Is this key currently up?
+    pub fn is_key_up(&self, key: Key) -> bool {
+        !self.now_keys.contains(&key)
+    }
+    /// Was this key just pressed on this frame?
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.now_keys.contains(&key) && !self.prev_keys.contains(&key)
+    }
+    /// Was this key just released on this frame?
+    pub fn is_key_released(&self, key: Key) -> bool {
+        !self.now_keys.contains(&key) && self.prev_keys.contains(&key)
+    }
+    /// Was this key down on the previous frame?
+    pub fn was_key_down(&self, key: Key) -> bool {
+        self.prev_keys.contains(&key)
+    }
+    /// Every key currently down, in no particular order
+    pub fn keys_down(&self) -> &[Key] {
+        &self.now_keys
+    }
+    /// Lets go of every key, e.g. before a replay sets the
+    /// state for the next tick.
+    pub fn release_all(&mut self) {
+        self.now_keys.clear();
+    }
+    /// Given two keys (a negative and positive direction), produce a
+    /// value between -1 and 1 based on which are currently held.
+    pub fn key_axis(&self, down: Key, up: Key) -> f32 {
+        (if self.is_key_down(down) { -1.0 } else { 0.0 })
+            + (if self.is_key_down(up) { 1.0 } else { 0.0 })
+    }
+    /// Cycle current state to previous state.
+    pub fn next_frame(&mut self) {
+        self.prev_keys.clear();
+        self.prev_keys.extend_from_slice(&self.now_keys);
+    }
+}
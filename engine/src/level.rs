@@ -1,35 +1,413 @@
-// use crate::geom::*;
+use crate::app::{Assets, SpriteSink};
+use crate::autotile::{neighbour_mask, TileSet};
 use crate::grid::{self, Grid};
-use crate::EntityType;
+use crate::tiled::TiledMap;
 use crate::Rect;
 use crate::TileData;
 use crate::Vec2;
-use crate::TILE_SZ;
-use frenderer::{
-    sprites::{SheetRegion, Transform},
-    Immediate,
-};
+use frenderer::sprites::{SheetRegion, Transform};
 use std::collections::HashMap;
 use std::str::FromStr;
 
-#[allow(dead_code)]
-pub struct Level {
+/// The part of a level file a [`LevelParseError`] was found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Metadata,
+    Legend,
+    Map,
+    Starts,
+}
+
+/// Where in a level file something went wrong.  Lines and columns
+/// count from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub section: Section,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LevelParseError {
+    /// The line ended before a required field
+    Missing {
+        at: Location,
+        what: &'static str,
+    },
+    /// A field that should be a number isn't one (or is out of range)
+    BadNumber {
+        at: Location,
+        what: &'static str,
+        text: String,
+    },
+    /// A second level name, bg, or legend entry for the same symbol
+    Duplicate {
+        at: Location,
+        what: &'static str,
+        text: String,
+    },
+    BadFlags {
+        at: Location,
+        flags: String,
+    },
+    TooManySymbols {
+        at: Location,
+    },
+    /// A map cell uses a symbol that isn't in the legend
+    UnknownSymbol {
+        at: Location,
+        symbol: String,
+    },
+    RowLength {
+        at: Location,
+        expected: usize,
+        found: usize,
+    },
+    RowCount {
+        at: Location,
+        expected: usize,
+        found: usize,
+    },
+    UnknownEntity {
+        at: Location,
+        name: String,
+    },
+    /// A start position outside the map
+    OutOfBounds {
+        at: Location,
+        x: u16,
+        y: u16,
+    },
+    /// Anything after the starts section
+    UnexpectedContent {
+        at: Location,
+    },
+    /// An autotile group with the wrong number of tiles for its set
+    AutotileCount {
+        at: Location,
+        expected: usize,
+        found: usize,
+    },
+    /// The file ended without something every level needs
+    Incomplete {
+        section: Section,
+        what: &'static str,
+    },
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {} ({:?} section)",
+            self.line, self.column, self.section
+        )
+    }
+}
+
+impl std::fmt::Display for LevelParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { at, what } => write!(f, "{at}: no {what}"),
+            Self::BadNumber { at, what, text } => {
+                write!(f, "{at}: couldn't parse {what} from {text:?}")
+            }
+            Self::Duplicate { at, what, text } => write!(f, "{at}: duplicate {what} {text:?}"),
+            Self::BadFlags { at, flags } => write!(
+                f,
+                "{at}: the only valid flags are o(pen) or s(olid), not {flags:?}"
+            ),
+            Self::TooManySymbols { at } => write!(f, "{at}: more than 256 legend symbols"),
+            Self::UnknownSymbol { at, symbol } => {
+                write!(f, "{at}: symbol {symbol:?} isn't in the legend")
+            }
+            Self::RowLength {
+                at,
+                expected,
+                found,
+            } => write!(f, "{at}: map row has {found} tiles, expected {expected}"),
+            Self::RowCount {
+                at,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{at}: map has at least {found} rows, expected {expected}"
+            ),
+            Self::UnknownEntity { at, name } => {
+                write!(f, "{at}: unrecognized entity type {name:?}")
+            }
+            Self::OutOfBounds { at, x, y } => write!(f, "{at}: start {x} {y} is outside the map"),
+            Self::UnexpectedContent { at } => {
+                write!(f, "{at}: unexpected content after parsing finished")
+            }
+            Self::AutotileCount {
+                at,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{at}: autotile group has {found} tiles, expected {expected}"
+            ),
+            Self::Incomplete { section, what } => {
+                write!(f, "level ended without {what} ({section:?} section)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelParseError {}
+
+enum State {
+    Metadata,
+    Legend,
+    Map,
+    Starts,
+    Done,
+}
+impl State {
+    fn next(self) -> Self {
+        match self {
+            Self::Metadata => Self::Legend,
+            Self::Legend => Self::Map,
+            Self::Map => Self::Starts,
+            Self::Starts => Self::Done,
+            Self::Done => Self::Done,
+        }
+    }
+}
+
+/// Whitespace-separated fields of one line, remembering where each
+/// one came from for error messages.
+struct Fields<'a> {
+    line: &'a str,
+    offset: usize,
+    line_no: usize,
+    section: Section,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line: &'a str, line_no: usize, section: Section) -> Self {
+        Self {
+            line,
+            offset: 0,
+            line_no,
+            section,
+        }
+    }
+    fn location(&self, offset: usize) -> Location {
+        Location {
+            line: self.line_no + 1,
+            column: offset + 1,
+            section: self.section,
+        }
+    }
+    fn try_next(&mut self) -> Option<(&'a str, Location)> {
+        let rest = &self.line[self.offset..];
+        let start = self.offset + rest.find(|c: char| !c.is_whitespace())?;
+        let len = self.line[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(self.line.len() - start);
+        self.offset = start + len;
+        Some((&self.line[start..self.offset], self.location(start)))
+    }
+    fn next(&mut self, what: &'static str) -> Result<(&'a str, Location), LevelParseError> {
+        self.try_next().ok_or(LevelParseError::Missing {
+            at: self.location(self.line.len()),
+            what,
+        })
+    }
+    fn number<T: FromStr>(&mut self, what: &'static str) -> Result<T, LevelParseError> {
+        let (text, at) = self.next(what)?;
+        T::from_str(text).map_err(|_| LevelParseError::BadNumber {
+            at,
+            what,
+            text: text.to_string(),
+        })
+    }
+}
+
+/// Tile size (in world units) for levels that don't declare one
+pub const DEFAULT_TILE_SZ: usize = 4;
+
+/// Depth of the tiles in levels whose layers don't declare one
+pub const DEFAULT_LAYER_DEPTH: u16 = 16;
+
+/// One grid of tiles in a level, with its own legend.  Layers draw in
+/// the order the level lists them, and only colliding layers count for
+/// [`Level::get_tile`] and the other collision queries.
+pub struct Layer {
     name: String,
-    bg: SheetRegion,
+    depth: u16,
+    visible: bool,
+    collides: bool,
     grid: Grid<u8>,
     tileset: Tileset,
-    starts: Vec<(EntityType, Vec2)>,
 }
 
-impl Level {
+impl Layer {
+    /// A layer drawing `tiles[t]` for each cell `t` of `grid`
+    pub fn new(
+        name: &str,
+        depth: u16,
+        visible: bool,
+        collides: bool,
+        grid: Grid<u8>,
+        tiles: Vec<TileData>,
+    ) -> Self {
+        assert!(
+            grid.row_iter()
+                .flatten()
+                .all(|t| (*t as usize) < tiles.len()),
+            "Layer {name} uses a tile it doesn't have"
+        );
+        Self {
+            name: name.to_string(),
+            depth,
+            visible,
+            collides,
+            grid,
+            tileset: Tileset { tiles },
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+    /// Hides or shows the layer; hidden layers still collide
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+    pub fn collides(&self) -> bool {
+        self.collides
+    }
+    /// The tile in grid cell `pos`, or `None` outside the map
+    pub fn get_tile(&self, pos: grid::Coord) -> Option<&TileData> {
+        self.grid
+            .get(pos.0, pos.1)
+            .map(|t| &self.tileset[*t as usize])
+    }
+}
+
+/// A layer as it's read in, before its autotiles are picked
+struct LayerInProgress {
+    name: String,
+    depth: u16,
+    visible: bool,
+    collides: bool,
+    /// Whether a `layer` line has named this layer yet
+    declared: bool,
+    legend: HashMap<String, (u8, TileData)>,
+    // each group's tile set and tiles, and the cells painted with it
+    groups: HashMap<String, (TileSet, Vec<u8>)>,
+    autotiled: Vec<(usize, String)>,
+    grid: Vec<u8>,
+}
+
+impl LayerInProgress {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            depth: DEFAULT_LAYER_DEPTH,
+            visible: true,
+            collides: true,
+            declared: false,
+            legend: HashMap::new(),
+            groups: HashMap::new(),
+            autotiled: vec![],
+            grid: vec![],
+        }
+    }
+    /// Reads `layer NAME [DEPTH] [hidden] [nocollide]`, after the word `layer`
+    fn declare(&mut self, chunks: &mut Fields) -> Result<(), LevelParseError> {
+        self.name = chunks.next("layer name")?.0.to_string();
+        self.declared = true;
+        while let Some((option, at)) = chunks.try_next() {
+            match option {
+                "hidden" => self.visible = false,
+                "nocollide" => self.collides = false,
+                depth => {
+                    self.depth = depth.parse().map_err(|_| LevelParseError::BadNumber {
+                        at,
+                        what: "layer depth",
+                        text: depth.to_string(),
+                    })?
+                }
+            }
+        }
+        Ok(())
+    }
+    fn finish(self, w: usize, h: usize) -> Result<Layer, LevelParseError> {
+        if self.legend.is_empty() {
+            return Err(LevelParseError::Incomplete {
+                section: Section::Legend,
+                what: "tile symbols",
+            });
+        }
+        if self.grid.len() != w * h {
+            return Err(LevelParseError::Incomplete {
+                section: Section::Map,
+                what: "map rows",
+            });
+        }
+        let mut grid = Grid::new(w, h, self.grid);
+        let picked: Vec<(usize, u8)> = self
+            .autotiled
+            .iter()
+            .map(|(idx, sym)| {
+                let (set, tiles) = &self.groups[sym];
+                let (x, y) = grid.index_to_coord(*idx).unwrap();
+                let mask = neighbour_mask(&grid, x, y, |tile| tiles.contains(tile));
+                (*idx, tiles[set.index(mask)])
+            })
+            .collect();
+        for (idx, tile) in picked {
+            grid[idx] = tile;
+        }
+        let mut tiles: Vec<(u8, TileData)> = self.legend.into_values().collect();
+        tiles.sort_by_key(|(num, _)| *num);
+        Ok(Layer {
+            name: self.name,
+            depth: self.depth,
+            visible: self.visible,
+            collides: self.collides,
+            grid,
+            tileset: Tileset {
+                tiles: tiles.into_iter().map(|(_num, val)| val).collect(),
+            },
+        })
+    }
+}
+
+/// A tile map plus the starting positions of the entities in it.
+/// Each game supplies its own entity type `E`, which is parsed from
+/// the entity names in the starts section with [`FromStr`].
+#[allow(dead_code)]
+pub struct Level<E> {
+    name: String,
+    tile_sz: usize,
+    width: usize,
+    height: usize,
+    bg: SheetRegion,
+    layers: Vec<Layer>,
+    starts: Vec<(E, Vec2)>,
+}
+
+impl<E: FromStr> Level<E> {
     /*
     We'll read from an ad hoc format like this, where FLAGS is either S (solid) or O (open) but could be other stuff later:
 
-    LEVELNAME W H
+    LEVELNAME W H [TILE_SZ]
     ====
     SYM FLAGS X Y W H
     SYM FLAGS X Y W H
     SYM FLAGS X Y W H
+    SYM TILESET SYM SYM SYM ...
     ====
     SYM SYM SYM SYM SYM
     SYM SYM SYM SYM SYM
@@ -42,246 +420,424 @@ impl Level {
     enemy X Y
     enemy X Y
     door LEVELNAME TO-X TO-Y X Y
-    you can add more types of thing if you want
+    you can add more types of thing if you want, as long as E can parse them
+
+    A legend line with a TILESET (edge16, blob47 or marching16, see
+    autotile::TileSet) instead of flags is an autotile group: it lists
+    the symbols of its tiles in the set's order, and each map cell
+    painted with the group's symbol gets whichever of those tiles fits
+    its neighbours.  Cells painted with the group or any of its tiles
+    count as neighbours, and so does everything off the edge of the map.
+    A tile with no width or height draws nothing.
+
+    A level can have more tile layers, each a legend and map of its own
+    that starts with a layer line, before or in among the starts:
+
+    ====
+    layer NAME [DEPTH] [hidden] [nocollide]
+    SYM FLAGS X Y W H
+    ====
+    SYM SYM SYM SYM SYM
+    ...
+    ====
+
+    The first legend can start with a layer line too; otherwise its
+    layer is called "map".  Layers are visible, collide, and have depth
+    16 unless they say otherwise.
     */
-    pub fn from_str(s: &str) -> Self {
-        enum State {
-            Metadata,
-            Legend,
-            Map,
-            Starts,
-            Done,
-        }
-        impl State {
-            fn next(self) -> Self {
-                match self {
-                    Self::Metadata => Self::Legend,
-                    Self::Legend => Self::Map,
-                    Self::Map => Self::Starts,
-                    Self::Starts => Self::Done,
-                    Self::Done => Self::Done,
-                }
-            }
+    pub fn parse(s: &str) -> Result<Self, LevelParseError> {
+        Self::parse_tiles(s, None)
+    }
+    /// Parses a level using `tile_sz` no matter what tile size the
+    /// level file declares.
+    pub fn parse_with_tile_size(s: &str, tile_sz: usize) -> Result<Self, LevelParseError> {
+        if tile_sz == 0 {
+            return Err(LevelParseError::BadNumber {
+                at: Location {
+                    line: 1,
+                    column: 1,
+                    section: Section::Metadata,
+                },
+                what: "tile size",
+                text: tile_sz.to_string(),
+            });
         }
+        Self::parse_tiles(s, Some(tile_sz))
+    }
+    fn parse_tiles(s: &str, tile_sz_override: Option<usize>) -> Result<Self, LevelParseError> {
         let mut state = State::Metadata;
         let mut name = None;
         let mut dims = None;
-        let mut legend: HashMap<String, (u8, TileData)> = std::collections::HashMap::new();
-        let mut grid = vec![];
+        let mut layers: Vec<Layer> = vec![];
+        let mut layer = LayerInProgress::new("map");
         let mut starts = vec![];
         let mut bg = SheetRegion::ZERO;
-        for line in s.lines() {
-            if line.is_empty() {
+        let mut tile_sz = tile_sz_override.unwrap_or(DEFAULT_TILE_SZ);
+        for (line_no, line) in s.lines().enumerate() {
+            if line.trim().is_empty() {
                 continue;
             } else if line.chars().all(|c| c == '=') {
                 state = state.next();
-            } else {
-                match state {
-                    State::Metadata => {
-                        let mut chunks = line.split_whitespace();
-                        let md = chunks
-                            .next()
-                            .expect("No metadata decl string in metadata line {line}");
-                        if md == "bg" {
-                            if bg.w != 0 {
-                                panic!("Two bg entries in metadata");
-                            }
-                            bg = SheetRegion::rect(
-                                u16::from_str(chunks.next().expect("No x in metadata line {line}"))
-                                    .expect("Couldn't parse x as u16 in {line}"),
-                                u16::from_str(chunks.next().expect("No y in metadata line {line}"))
-                                    .expect("Couldn't parse y as u16 in {line}"),
-                                i16::from_str(
-                                    chunks.next().expect("No width in metadata line {line}"),
-                                )
-                                .expect("Couldn't parse width as i16 in {line}"),
-                                i16::from_str(
-                                    chunks.next().expect("No height in metadata line {line}"),
-                                )
-                                .expect("Couldn't parse height as i16 in {line}"),
-                            )
-                            .with_depth(u16::MAX - 1);
-                        } else {
-                            if name.is_some() {
-                                panic!("Two name entries in metadata");
-                            }
-                            name = Some(md.to_string());
-                            dims = Some((
-                                u16::from_str(
-                                    chunks.next().expect("No width in metadata line {line}"),
-                                )
-                                .expect("Couldn't parse width as u16 in {line}"),
-                                u16::from_str(
-                                    chunks.next().expect("No height in metadata line {line}"),
-                                )
-                                .expect("Couldn't parse height as u16 in {line}"),
-                            ));
+                continue;
+            }
+            let section = match state {
+                State::Metadata => Section::Metadata,
+                State::Legend => Section::Legend,
+                State::Map => Section::Map,
+                State::Starts => Section::Starts,
+                State::Done => {
+                    return Err(LevelParseError::UnexpectedContent {
+                        at: Location {
+                            line: line_no + 1,
+                            column: 1,
+                            section: Section::Starts,
+                        },
+                    })
+                }
+            };
+            let mut chunks = Fields::new(line, line_no, section);
+            match state {
+                State::Metadata => {
+                    let (md, at) = chunks.next("metadata decl")?;
+                    if md == "bg" {
+                        if bg.w != 0 {
+                            return Err(LevelParseError::Duplicate {
+                                at,
+                                what: "bg",
+                                text: md.to_string(),
+                            });
                         }
-                    }
-                    State::Legend => {
-                        let mut chunks = line.split_whitespace();
-                        let sym = chunks.next().expect("Couldn't get tile symbol in {line}");
-                        assert!(!legend.contains_key(sym), "Symbol {sym} already in legend");
-                        let flags = chunks
-                            .next()
-                            .expect("Couldn't get tile flags in {line}")
-                            .to_lowercase();
-                        assert!(flags == "o" || flags == "s", "The only valid flags are o(pen) or s(olid), but you could parse other kinds here in {line}");
-                        let x =
-                            u16::from_str(chunks.next().expect("No sheet x in legend line {line}"))
-                                .expect("Couldn't parse sheet x as u16 in {line}");
-                        let y =
-                            u16::from_str(chunks.next().expect("No sheet y in legend line {line}"))
-                                .expect("Couldn't parse sheet y as u16 in {line}");
-                        let w =
-                            i16::from_str(chunks.next().expect("No sheet w in legend line {line}"))
-                                .expect("Couldn't parse sheet w as i16 in {line}");
-                        let h =
-                            i16::from_str(chunks.next().expect("No sheet h in legend line {line}"))
-                                .expect("Couldn't parse sheet h as i16 in {line}");
-                        let data = TileData {
-                            solid: flags == "s",
-                            sheet_region: SheetRegion::new(0, x, y, 16, w, h),
+                        bg = SheetRegion::rect(
+                            chunks.number("x")?,
+                            chunks.number("y")?,
+                            chunks.number("width")?,
+                            chunks.number("height")?,
+                        )
+                        .with_depth(u16::MAX - 1);
+                    } else {
+                        if name.is_some() {
+                            return Err(LevelParseError::Duplicate {
+                                at,
+                                what: "level name",
+                                text: md.to_string(),
+                            });
+                        }
+                        name = Some(md.to_string());
+                        // an empty map has nowhere to put anything
+                        let mut size = |what| {
+                            let (text, at) = chunks.next(what)?;
+                            u16::from_str(text).ok().filter(|n| *n > 0).ok_or_else(|| {
+                                LevelParseError::BadNumber {
+                                    at,
+                                    what,
+                                    text: text.to_string(),
+                                }
+                            })
                         };
-                        legend.insert(sym.to_string(), (legend.len() as u8, data));
+                        dims = Some((size("width")?, size("height")?));
+                        if let Some((text, at)) = chunks.try_next() {
+                            let declared = usize::from_str(text)
+                                .ok()
+                                .filter(|sz| *sz > 0)
+                                .ok_or_else(|| LevelParseError::BadNumber {
+                                    at,
+                                    what: "tile size",
+                                    text: text.to_string(),
+                                })?;
+                            tile_sz = tile_sz_override.unwrap_or(declared);
+                        }
+                    }
+                }
+                State::Legend => {
+                    let (sym, at) = chunks.next("tile symbol")?;
+                    let legend = &layer.legend;
+                    if sym == "layer"
+                        && !layer.declared
+                        && legend.is_empty()
+                        && layer.groups.is_empty()
+                    {
+                        layer.declare(&mut chunks)?;
+                        continue;
+                    }
+                    if legend.contains_key(sym) || layer.groups.contains_key(sym) {
+                        return Err(LevelParseError::Duplicate {
+                            at,
+                            what: "legend symbol",
+                            text: sym.to_string(),
+                        });
+                    }
+                    if legend.len() > u8::MAX as usize {
+                        return Err(LevelParseError::TooManySymbols { at });
                     }
-                    State::Map => {
-                        let old_len = grid.len();
-                        grid.extend(line.split_whitespace().map(|sym| legend[sym].0));
-                        assert_eq!(
-                            old_len + dims.unwrap().0 as usize,
-                            grid.len(),
-                            "map line is too short: {line} for map dims {dims:?}"
-                        );
+                    let (flags, at) = chunks.next("tile flags")?;
+                    if let Ok(set) = TileSet::from_str(flags) {
+                        let mut tiles = vec![];
+                        while let Some((tile, tile_at)) = chunks.try_next() {
+                            let Some((tile, _)) = legend.get(tile) else {
+                                return Err(LevelParseError::UnknownSymbol {
+                                    at: tile_at,
+                                    symbol: tile.to_string(),
+                                });
+                            };
+                            tiles.push(*tile);
+                        }
+                        if tiles.len() != set.tile_count() {
+                            return Err(LevelParseError::AutotileCount {
+                                at,
+                                expected: set.tile_count(),
+                                found: tiles.len(),
+                            });
+                        }
+                        layer.groups.insert(sym.to_string(), (set, tiles));
+                        continue;
+                    }
+                    let flags = flags.to_lowercase();
+                    // The only valid flags are o(pen) or s(olid), but you could parse other kinds here
+                    if flags != "o" && flags != "s" {
+                        return Err(LevelParseError::BadFlags { at, flags });
                     }
-                    State::Starts => {
-                        let mut chunks = line.split_whitespace();
-                        let etype = chunks
-                            .next()
-                            .expect("Couldn't get entity start type {line}");
-                        let etype = match etype {
-                            "snake" => EntityType::Snake,
-                            "apple" => EntityType::Food,
-                            "gold" => EntityType::Gold,
-                            "silver" => EntityType::Silver,
-                            "gem" => EntityType::Gem,
-                            "rock" => EntityType::Rock,
-                            "claw" => EntityType::Claw,
-                            _ => panic!("Unrecognized entity type in {line}"),
+                    let x = chunks.number("sheet x")?;
+                    let y = chunks.number("sheet y")?;
+                    let w = chunks.number("sheet w")?;
+                    let h = chunks.number("sheet h")?;
+                    let data = TileData {
+                        solid: flags == "s",
+                        sheet_region: SheetRegion::new(0, x, y, layer.depth, w, h),
+                    };
+                    let num = legend.len() as u8;
+                    layer.legend.insert(sym.to_string(), (num, data));
+                }
+                State::Map => {
+                    let Some((w, h)) = dims else {
+                        return Err(LevelParseError::Incomplete {
+                            section: Section::Metadata,
+                            what: "level name and size",
+                        });
+                    };
+                    let grid = &mut layer.grid;
+                    let at = chunks.location(0);
+                    if grid.len() >= w as usize * h as usize {
+                        return Err(LevelParseError::RowCount {
+                            at,
+                            expected: h as usize,
+                            found: grid.len() / w as usize + 1,
+                        });
+                    }
+                    let old_len = grid.len();
+                    while let Some((sym, at)) = chunks.try_next() {
+                        if let Some((_, tiles)) = layer.groups.get(sym) {
+                            // picked once the whole map is in
+                            layer.autotiled.push((grid.len(), sym.to_string()));
+                            grid.push(tiles[0]);
+                            continue;
+                        }
+                        let Some((tile, _)) = layer.legend.get(sym) else {
+                            return Err(LevelParseError::UnknownSymbol {
+                                at,
+                                symbol: sym.to_string(),
+                            });
                         };
-                        let x =
-                            u16::from_str(chunks.next().expect("No x coord in start line {line}"))
-                                .expect("Couldn't parse x coord as u16 in {line}");
-                        let y =
-                            u16::from_str(chunks.next().expect("No y coord in start line {line}"))
-                                .expect("Couldn't parse y coord as u16 in {line}");
-                        starts.push((
-                            etype,
-                            Vec2 {
-                                x: (x as usize * TILE_SZ) as f32 + TILE_SZ as f32 / 2.0,
-                                y: ((dims.unwrap().1 - y) as usize * TILE_SZ) as f32
-                                    - TILE_SZ as f32 / 2.0,
-                            },
-                        ));
+                        grid.push(*tile);
                     }
-                    State::Done => {
-                        panic!("Unexpected file content after parsing finished in {line}")
+                    if grid.len() - old_len != w as usize {
+                        return Err(LevelParseError::RowLength {
+                            at,
+                            expected: w as usize,
+                            found: grid.len() - old_len,
+                        });
                     }
                 }
+                State::Starts => {
+                    let (etype, at) = chunks.next("entity start type")?;
+                    let Some((w, h)) = dims else {
+                        return Err(LevelParseError::Incomplete {
+                            section: Section::Metadata,
+                            what: "level name and size",
+                        });
+                    };
+                    if etype == "layer" {
+                        let done = std::mem::replace(&mut layer, LayerInProgress::new(""));
+                        layers.push(done.finish(w as usize, h as usize)?);
+                        layer.declare(&mut chunks)?;
+                        if layers.iter().any(|l| l.name == layer.name) {
+                            return Err(LevelParseError::Duplicate {
+                                at,
+                                what: "layer name",
+                                text: layer.name,
+                            });
+                        }
+                        state = State::Legend;
+                        continue;
+                    }
+                    let etype = E::from_str(etype).map_err(|_| LevelParseError::UnknownEntity {
+                        at,
+                        name: etype.to_string(),
+                    })?;
+                    let x = chunks.number::<u16>("x coord")?;
+                    let y = chunks.number::<u16>("y coord")?;
+                    if x >= w || y >= h {
+                        return Err(LevelParseError::OutOfBounds { at, x, y });
+                    }
+                    starts.push((
+                        etype,
+                        Vec2 {
+                            x: (x as usize * tile_sz) as f32 + tile_sz as f32 / 2.0,
+                            y: ((h - y) as usize * tile_sz) as f32 - tile_sz as f32 / 2.0,
+                        },
+                    ));
+                }
+                State::Done => unreachable!(),
             }
         }
-        assert_ne!(name, None);
-        let name = name.unwrap();
-        assert_ne!(dims, None);
-        let (w, h) = dims.unwrap();
-        assert!(!legend.is_empty());
-        assert_eq!(grid.len(), w as usize * h as usize);
-        let mut tiles: Vec<(u8, TileData)> = legend.into_values().collect();
-        tiles.sort_by_key(|(num, _)| *num);
-        Self {
+        let (Some(name), Some((w, h))) = (name, dims) else {
+            return Err(LevelParseError::Incomplete {
+                section: Section::Metadata,
+                what: "level name and size",
+            });
+        };
+        let (w, h) = (w as usize, h as usize);
+        layers.push(layer.finish(w, h)?);
+        Ok(Self {
             bg,
+            name,
+            tile_sz,
+            width: w,
+            height: h,
+            layers,
+            starts,
+        })
+    }
+}
+
+impl<E> Level<E> {
+    /// A level made of `layers`, which must all be the same size, with
+    /// no background
+    pub fn new(name: &str, tile_sz: usize, layers: Vec<Layer>, starts: Vec<(E, Vec2)>) -> Self {
+        let first = layers.first().expect("Level has no layers");
+        let (width, height) = (first.grid.width(), first.grid.height());
+        assert!(
+            layers
+                .iter()
+                .all(|l| (l.grid.width(), l.grid.height()) == (width, height)),
+            "Level {name} has layers of different sizes"
+        );
+        Self {
             name: name.to_string(),
-            grid: Grid::new(w as usize, h as usize, grid),
-            tileset: Tileset {
-                tiles: tiles.into_iter().map(|(_num, val)| val).collect(),
-            },
+            tile_sz,
+            width,
+            height,
+            bg: SheetRegion::ZERO,
+            layers,
             starts,
         }
     }
+    /// One sprite per cell of each visible layer, plus the background
     pub fn sprite_count(&self) -> usize {
-        self.grid.width() * self.grid.height() + 1
+        let visible = self.layers.iter().filter(|l| l.visible).count();
+        visible * self.width * self.height + 1
     }
-    pub fn render_immediate(&self, frend: &mut Immediate) -> usize {
+    pub fn render_immediate(&self, frend: &mut impl SpriteSink) -> usize {
         let len = self.sprite_count();
         let (trfs, uvs) = frend.draw_sprites(0, len);
         self.render_into(trfs, uvs)
     }
+    /// Draws the visible layers in order, then the background
     pub fn render_into(&self, trfs: &mut [Transform], uvs: &mut [SheetRegion]) -> usize {
-        let w = self.grid.width();
-        let h = self.grid.height();
+        let w = self.width;
+        let h = self.height;
+        let tile_sz = self.tile_sz;
         assert_eq!(trfs.len(), uvs.len());
-        assert_eq!(trfs.len(), w * h + 1);
-        for ((y, row), (trfs, uvs)) in self
-            .grid
-            .row_iter()
-            .enumerate()
-            .zip(trfs.chunks_exact_mut(w).zip(uvs.chunks_exact_mut(w)))
-        {
-            for ((x, tile), (trf, uv)) in row
-                .iter()
+        assert_eq!(trfs.len(), self.sprite_count());
+        let layers = self.layers.iter().filter(|l| l.visible);
+        let cells = layers.flat_map(|layer| {
+            layer
+                .grid
+                .row_iter()
                 .enumerate()
-                .zip(trfs.iter_mut().zip(uvs.iter_mut()))
-            {
-                // NOTE: we're converting from grid coordinates to "sprite center coordinates", so we have to flip y...
-                let y = h - y - 1;
-                *trf = Transform {
-                    // and multiply by tile sz *and* offset by half tile sz
-                    x: (x * TILE_SZ + TILE_SZ / 2) as f32,
-                    y: (y * TILE_SZ + TILE_SZ / 2) as f32,
-                    w: TILE_SZ as u16,
-                    h: TILE_SZ as u16,
-                    rot: 0.0,
-                };
-                *uv = self.tileset[*tile as usize].sheet_region;
-            }
+                .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, t)| (x, y, *t)))
+                .map(|(x, y, t)| (x, y, layer.tileset[t as usize].sheet_region))
+        });
+        for ((x, y, region), (trf, uv)) in cells.zip(trfs.iter_mut().zip(uvs.iter_mut())) {
+            // NOTE: we're converting from grid coordinates to "sprite center coordinates", so we have to flip y...
+            let y = h - y - 1;
+            // blank tiles get no size, so they draw nothing
+            let sz = if region.w == 0 || region.h == 0 {
+                0
+            } else {
+                tile_sz as u16
+            };
+            *trf = Transform {
+                // and multiply by tile sz *and* offset by half tile sz
+                x: (x * tile_sz) as f32 + tile_sz as f32 / 2.0,
+                y: (y * tile_sz) as f32 + tile_sz as f32 / 2.0,
+                w: sz,
+                h: sz,
+                rot: 0.0,
+            };
+            *uv = region;
         }
         if self.bg.w != 0 {
             trfs[trfs.len() - 1] = Transform {
-                x: (self.grid.width() * TILE_SZ) as f32 / 2.0,
-                y: (self.grid.height() * TILE_SZ) as f32 / 2.0,
-                w: (self.grid.width() as u16 * TILE_SZ as u16),
-                h: (self.grid.height() as u16 * TILE_SZ as u16),
+                x: (w * tile_sz) as f32 / 2.0,
+                y: (h * tile_sz) as f32 / 2.0,
+                w: (w * tile_sz) as u16,
+                h: (h * tile_sz) as u16,
                 rot: 0.0,
             };
             uvs[uvs.len() - 1] = self.bg;
         }
-        w * h + 1
+        trfs.len()
     }
     #[allow(dead_code)]
     pub fn name(&self) -> &str {
         &self.name
     }
-    pub fn starts(&self) -> &[(EntityType, Vec2)] {
+    pub fn starts(&self) -> &[(E, Vec2)] {
         &self.starts
     }
+    /// Every tile layer, in drawing order
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.name == name)
+    }
     pub fn get_tile_at(&self, pos: Vec2) -> Option<&TileData> {
-        let (gx, gy) = self.world_to_grid(pos);
-        self.grid.get(gx, gy).map(|t| &self.tileset[*t as usize])
+        self.get_tile(self.world_to_grid(pos))
+    }
+    /// The tile in grid cell `pos` that things collide with, or `None`
+    /// outside the map: a solid tile from any colliding layer, or else
+    /// the first colliding layer's tile
+    pub fn get_tile(&self, pos: grid::Coord) -> Option<&TileData> {
+        let mut tiles = self
+            .layers
+            .iter()
+            .filter(|l| l.collides)
+            .filter_map(|l| l.get_tile(pos));
+        let first = tiles.next()?;
+        Some(if first.solid {
+            first
+        } else {
+            tiles.find(|t| t.solid).unwrap_or(first)
+        })
     }
     pub fn tile_index_at(&self, pos: Vec2) -> Option<usize> {
         let (gx, gy) = self.world_to_grid(pos);
-        self.grid.xy_to_index(gx, gy)
+        (gx < self.width && gy < self.height).then_some(gy * self.width + gx)
     }
     pub fn grid_to_world(&self, pos: grid::Coord) -> Vec2 {
         Vec2 {
-            x: pos.0 as f32 * TILE_SZ as f32,
-            y: (self.grid.height() - pos.1 - 1) as f32 * TILE_SZ as f32,
+            x: pos.0 as f32 * self.tile_sz as f32,
+            y: (self.height - pos.1 - 1) as f32 * self.tile_sz as f32,
         }
     }
     pub fn world_to_grid(&self, pos: Vec2) -> grid::Coord {
         (
-            (pos.x / TILE_SZ as f32) as usize,
-            (((self.grid.height() as f32 * TILE_SZ as f32) - pos.y - 1.0) / TILE_SZ as f32)
+            (pos.x / self.tile_sz as f32) as usize,
+            (((self.height as f32 * self.tile_sz as f32) - pos.y - 1.0) / self.tile_sz as f32)
                 as usize,
         )
     }
@@ -296,26 +852,53 @@ impl Level {
         });
         ((b.max(1) - 1)..(t + 2)).flat_map(move |row| {
             ((l.max(1) - 1)..(r + 2)).filter_map(move |col| {
-                self.grid.get(col, row).map(|tile_dat| {
+                self.get_tile((col, row)).map(|tile_dat| {
                     let world = self.grid_to_world((col, row));
                     (
                         Rect {
                             x: world.x,
                             y: world.y,
-                            w: TILE_SZ as u16,
-                            h: TILE_SZ as u16,
+                            w: self.tile_sz as u16,
+                            h: self.tile_sz as u16,
                         },
-                        &self.tileset[*tile_dat as usize],
+                        tile_dat,
                     )
                 })
             })
         })
     }
+    /// Width and height of one tile in world units
+    pub fn tile_size(&self) -> usize {
+        self.tile_sz
+    }
     pub fn width(&self) -> usize {
-        self.grid.width()
+        self.width
     }
     pub fn height(&self) -> usize {
-        self.grid.height()
+        self.height
+    }
+}
+
+impl<E: FromStr> Level<E> {
+    /// Loads the level asset `name`, from `name.txt` in the format
+    /// above or else from a Tiled map, `name.tmx` or `name.tmj` (see
+    /// [`crate::tiled`]).  Panics if it's missing or malformed.
+    pub fn load(cache: &Assets, name: &str) -> Self {
+        if let Ok(text) = cache.load::<String>(name) {
+            return Self::parse(&text.read())
+                .unwrap_or_else(|e| panic!("Couldn't parse {name}.txt: {e}"));
+        }
+        let map = cache
+            .load::<TiledMap>(name)
+            .unwrap_or_else(|_| panic!("Couldn't access {name}.txt, .tmx or .tmj"));
+        Self::from_tiled(&map.read().0).unwrap_or_else(|e| panic!("Couldn't import {name}: {e}"))
+    }
+}
+
+impl<E: FromStr> FromStr for Level<E> {
+    type Err = LevelParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
     }
 }
 
@@ -329,3 +912,321 @@ impl std::ops::Index<usize> for Tileset {
         &self.tiles[index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[derive(Debug, PartialEq)]
+    enum Entity {
+        Snake,
+    }
+    impl FromStr for Entity {
+        type Err = ();
+        fn from_str(s: &str) -> Result<Self, ()> {
+            match s {
+                "snake" => Ok(Entity::Snake),
+                _ => Err(()),
+            }
+        }
+    }
+    type Level = super::Level<Entity>;
+    const LEVEL: &str = "test 3 2
+====
+. o 0 0 4 4
+x s 4 0 4 4
+====
+x . x
+x x x
+====
+snake 1 0
+";
+    #[test]
+    fn test_parse() {
+        let level = Level::parse(LEVEL).unwrap();
+        assert_eq!(level.name(), "test");
+        assert_eq!((level.width(), level.height()), (3, 2));
+        assert_eq!(level.starts().len(), 1);
+        assert_eq!(level.starts()[0], (Entity::Snake, Vec2 { x: 6.0, y: 6.0 }));
+        assert!(!level.get_tile_at(Vec2 { x: 6.0, y: 6.0 }).unwrap().solid);
+        assert!(level.get_tile_at(Vec2 { x: 2.0, y: 2.0 }).unwrap().solid);
+        assert!(!level.get_tile((1, 0)).unwrap().solid);
+        assert!(level.get_tile((1, 1)).unwrap().solid);
+        assert!(level.get_tile((3, 0)).is_none());
+    }
+    #[test]
+    fn test_tile_size() {
+        assert_eq!(Level::parse(LEVEL).unwrap().tile_size(), DEFAULT_TILE_SZ);
+        let level = Level::parse(&LEVEL.replace("test 3 2", "test 3 2 8")).unwrap();
+        assert_eq!(level.tile_size(), 8);
+        assert_eq!(level.starts()[0].1, Vec2 { x: 12.0, y: 12.0 });
+        assert_eq!(level.grid_to_world((2, 0)), Vec2 { x: 16.0, y: 8.0 });
+        assert_eq!(level.world_to_grid(Vec2 { x: 20.0, y: 9.0 }), (2, 0));
+        // tiles_within pads the rect by a tile on each side
+        assert_eq!(
+            level
+                .tiles_within(Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    w: 8,
+                    h: 8
+                })
+                .count(),
+            6
+        );
+        let mut trfs = vec![Transform::ZERO; level.sprite_count()];
+        let mut uvs = vec![SheetRegion::ZERO; level.sprite_count()];
+        level.render_into(&mut trfs, &mut uvs);
+        assert_eq!((trfs[0].x, trfs[0].y, trfs[0].w), (4.0, 12.0, 8));
+        let level =
+            Level::parse_with_tile_size(&LEVEL.replace("test 3 2", "test 3 2 8"), 16).unwrap();
+        assert_eq!(level.tile_size(), 16);
+        assert_eq!(level.starts()[0].1, Vec2 { x: 24.0, y: 24.0 });
+        assert!(matches!(
+            Level::parse(&LEVEL.replace("test 3 2", "test 3 2 0")),
+            Err(LevelParseError::BadNumber {
+                what: "tile size",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Level::parse_with_tile_size(LEVEL, 0),
+            Err(LevelParseError::BadNumber {
+                what: "tile size",
+                ..
+            })
+        ));
+    }
+    #[test]
+    fn test_autotile() {
+        // a room walled with one symbol, plus a pillar in the middle
+        let sides = ["none", "u", "r", "ur", "d", "ud", "rd", "urd"];
+        let sides = sides
+            .iter()
+            .chain(&["l", "ul", "rl", "url", "dl", "udl", "rdl", "all"]);
+        let mut legend = String::from(". o 0 0 4 4\n");
+        for (i, name) in sides.clone().enumerate() {
+            legend += &format!("{name} s {} 0 4 4\n", i * 4);
+        }
+        let names: Vec<&str> = sides.copied().collect();
+        let level = format!(
+            "room 5 4\n====\n{legend}# edge16 {}\n====\n# # # # #\n# . . . #\n# . # . #\n# # # # #\n",
+            names.join(" ")
+        );
+        let level = Level::parse(&level).unwrap();
+        let name_at = |x, y| names[level.get_tile((x, y)).unwrap().sheet_region.x as usize / 4];
+        assert_eq!(name_at(0, 0), "all");
+        assert_eq!(name_at(1, 0), "url");
+        assert_eq!(name_at(4, 1), "urd");
+        assert_eq!(name_at(0, 1), "udl");
+        assert_eq!(name_at(2, 2), "d");
+        assert_eq!(name_at(2, 3), "all");
+        assert!(!level.get_tile((1, 1)).unwrap().solid);
+
+        // blob and marching sets pick from the corners too
+        let blob_legend: String = (0..47).map(|i| format!("b{i} s {i} 4 4 4\n")).collect();
+        let blob_tiles: Vec<String> = (0..47).map(|i| format!("b{i}")).collect();
+        let level = format!(
+            "blob 3 3\n====\n. o 0 0 4 4\n{blob_legend}# blob47 {}\n====\n# # #\n# # .\n# . .\n",
+            blob_tiles.join(" ")
+        );
+        let level = Level::parse(&level).unwrap();
+        let blob_at = |x, y| level.get_tile((x, y)).unwrap().sheet_region.x as usize;
+        assert_eq!(blob_at(0, 0), TileSet::Blob47.index(u8::MAX));
+        assert_eq!(blob_at(1, 1), TileSet::Blob47.index(1 | 2 | 64 | 128));
+        assert_eq!(
+            blob_at(1, 0),
+            TileSet::Blob47.index(1 | 2 | 4 | 16 | 32 | 64 | 128)
+        );
+
+        let err = |s: &str| Level::parse(s).err().unwrap();
+        assert!(matches!(
+            err("t 1 1\n====\nx s 0 0 4 4\n# marching16 x x\n====\n#\n"),
+            LevelParseError::AutotileCount {
+                expected: 16,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            err("t 1 1\n====\nx s 0 0 4 4\n# edge16 x y\n====\n#\n"),
+            LevelParseError::UnknownSymbol { .. }
+        ));
+    }
+    const LAYERED: &str = "layered 3 2
+====
+layer ground 100 nocollide
+. o 0 0 4 4
+g o 4 0 4 4
+====
+g . .
+. . g
+====
+snake 1 0
+layer walls
+_ o 0 0 0 0
+x s 8 0 4 4
+====
+x _ x
+_ _ x
+====
+layer overlay 1 hidden nocollide
+x s 12 0 4 4
+====
+x x x
+x x x
+";
+    #[test]
+    fn test_layers() {
+        let mut level = Level::parse(LAYERED).unwrap();
+        let names: Vec<&str> = level.layers().iter().map(|l| l.name()).collect();
+        assert_eq!(names, ["ground", "walls", "overlay"]);
+        assert_eq!(level.layer("ground").unwrap().depth(), 100);
+        assert_eq!(level.layer("walls").unwrap().depth(), DEFAULT_LAYER_DEPTH);
+        assert!(!level.layer("overlay").unwrap().is_visible());
+        assert_eq!(level.starts().len(), 1);
+        // only the walls collide
+        assert!(level.get_tile((0, 0)).unwrap().solid);
+        assert!(!level.get_tile((1, 0)).unwrap().solid);
+        assert!(level.get_tile((3, 0)).is_none());
+        let everything = Rect {
+            x: 0.0,
+            y: 0.0,
+            w: 12,
+            h: 8,
+        };
+        let solid = level.tiles_within(everything).filter(|(_, t)| t.solid);
+        assert_eq!(solid.count(), 3);
+        // ground then walls, with the blank wall tiles drawn at no size
+        assert_eq!(level.sprite_count(), 2 * 6 + 1);
+        let mut trfs = vec![Transform::ZERO; level.sprite_count()];
+        let mut uvs = vec![SheetRegion::ZERO; level.sprite_count()];
+        level.render_into(&mut trfs, &mut uvs);
+        assert_eq!((uvs[0].x, uvs[0].depth), (4, 100));
+        assert_eq!(
+            (uvs[6].x, uvs[6].depth, trfs[6].w),
+            (8, DEFAULT_LAYER_DEPTH, 4)
+        );
+        assert_eq!(trfs[7].w, 0);
+        level.layer_mut("overlay").unwrap().set_visible(true);
+        assert_eq!(level.sprite_count(), 3 * 6 + 1);
+
+        // a plain level is one layer called map
+        let level = Level::parse(LEVEL).unwrap();
+        assert_eq!(level.layers().len(), 1);
+        assert_eq!(level.layers()[0].name(), "map");
+        assert!(level.layers()[0].collides());
+
+        let err = |s: String| Level::parse(&s).err().unwrap();
+        assert!(matches!(
+            err(LAYERED.replace("layer overlay", "layer walls")),
+            LevelParseError::Duplicate {
+                what: "layer name",
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(LAYERED.replace("ground 100", "ground deep")),
+            LevelParseError::BadNumber {
+                what: "layer depth",
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(LAYERED.replace("x x x\nx x x\n", "x x x\n")),
+            LevelParseError::Incomplete {
+                section: Section::Map,
+                ..
+            }
+        ));
+    }
+    #[test]
+    fn test_parse_errors() {
+        let err = |s: String| Level::parse(&s).err().unwrap();
+        assert_eq!(
+            err(LEVEL.replace("x . x", "x . y")),
+            LevelParseError::UnknownSymbol {
+                at: Location {
+                    line: 6,
+                    column: 5,
+                    section: Section::Map
+                },
+                symbol: "y".to_string()
+            }
+        );
+        assert!(matches!(
+            err(LEVEL.replace("x . x", "x .")),
+            LevelParseError::RowLength {
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace("x x x\n", "x x x\nx x x\n")),
+            LevelParseError::RowCount { .. }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace("snake", "dragon")),
+            LevelParseError::UnknownEntity { .. }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace("snake 1 0", "snake 1 5")),
+            LevelParseError::OutOfBounds { x: 1, y: 5, .. }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace("test 3 2", "test 3 2\nbg 0 0 4 4\nbg 0 0 4 4")),
+            LevelParseError::Duplicate { what: "bg", .. }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace("x s 4 0 4 4", "x s 4 zero 4 4")),
+            LevelParseError::BadNumber {
+                what: "sheet y",
+                at: Location {
+                    line: 4,
+                    column: 7,
+                    ..
+                },
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace(". o 0 0 4 4", ". o")),
+            LevelParseError::Missing {
+                what: "sheet x",
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(LEVEL.replace("x x x\n", "")),
+            LevelParseError::Incomplete {
+                section: Section::Map,
+                ..
+            }
+        ));
+        // starts with no header before them
+        assert!(matches!(
+            err("====\n====\n====\nsnake 1 0\n".to_string()),
+            LevelParseError::Incomplete {
+                section: Section::Metadata,
+                ..
+            }
+        ));
+        // a map with no width or height
+        for header in ["t 0 5", "t 5 0"] {
+            assert!(matches!(
+                err(format!("{header}\n====\n. o 0 0 4 4\n====\n.\n")),
+                LevelParseError::BadNumber {
+                    at: Location {
+                        section: Section::Metadata,
+                        ..
+                    },
+                    ..
+                }
+            ));
+        }
+        assert_eq!(
+            err(LEVEL.replace(". o", ". q")).to_string(),
+            "line 3, column 3 (Legend section): the only valid flags are o(pen) or s(olid), not \"q\""
+        );
+    }
+}
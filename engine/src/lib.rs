@@ -1,29 +1,9 @@
-use frenderer::{
-    input::{Input, Key},
-    sprites::{Camera2D, SheetRegion, Transform},
-    wgpu, Renderer,
-};
-use std::collections::HashMap;
-use std::str::FromStr;
-
-const FOOD: [SheetRegion; 4] = [
-    SheetRegion::rect(533 + 16 * 2, 39, 16, 16),
-    SheetRegion::rect(533 + 16, 39, 16, 16),
-    SheetRegion::rect(533, 39, 16, 16),
-    SheetRegion::rect(533 + 16 * 3, 39, 16, 16),
-];
-
-const SNAKE: [SheetRegion; 4] = [
-    SheetRegion::rect(533 + 16 * 2, 39, 16, 16),
-    SheetRegion::rect(533 + 16, 39, 16, 16),
-    SheetRegion::rect(533, 39, 16, 16),
-    SheetRegion::rect(533 + 16 * 3, 39, 16, 16),
-];
+use frenderer::sprites::SheetRegion;
 
 #[derive(Clone, Copy, Debug)]
 pub struct TileData {
-    solid: bool,
-    sheet_region: SheetRegion,
+    pub solid: bool,
+    pub sheet_region: SheetRegion,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -44,45 +24,70 @@ impl Dir {
             Dir::Left => Vec2 { x: -1.0, y: 0.0 },
         }
     }
+    pub fn opposite(self) -> Dir {
+        match self {
+            Dir::Up => Dir::Down,
+            Dir::Right => Dir::Left,
+            Dir::Down => Dir::Up,
+            Dir::Left => Dir::Right,
+        }
+    }
+    /// The grid cell one step from `pos` in this direction.  Grid rows
+    /// count down from the top, so `Up` decreases `y`.  `None` if the
+    /// step would leave the grid on the top or left.
+    pub fn step(self, (x, y): grid::Coord) -> Option<grid::Coord> {
+        match self {
+            Dir::Up => Some((x, y.checked_sub(1)?)),
+            Dir::Right => Some((x + 1, y)),
+            Dir::Down => Some((x, y + 1)),
+            Dir::Left => Some((x.checked_sub(1)?, y)),
+        }
+    }
 }
 
-const TILE_SZ: usize = 4;
-const W: usize = 320;
-const H: usize = 240;
-
-pub mod grid;
-pub mod level;
-
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub struct Vec2 {
-    pub x: f32,
-    pub y: f32,
+impl std::fmt::Display for Dir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Dir::Up => "up",
+            Dir::Down => "down",
+            Dir::Left => "left",
+            Dir::Right => "right",
+        })
+    }
 }
 
-impl std::ops::Add for Vec2 {
-    type Output = Vec2;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        Self::Output {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
+impl std::str::FromStr for Dir {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "up" => Ok(Dir::Up),
+            "down" => Ok(Dir::Down),
+            "left" => Ok(Dir::Left),
+            "right" => Ok(Dir::Right),
+            _ => Err(()),
         }
     }
 }
 
-pub struct Tileset {
-    tiles: Vec<TileData>,
-}
+pub mod actions;
+pub mod ai;
+pub mod anim;
+pub mod app;
+pub mod autotile;
+pub mod campaign;
+pub mod collision;
+pub mod geom;
+pub mod grid;
+pub mod headless;
+pub mod input;
+pub mod level;
+pub mod replay;
+pub mod save;
+pub mod scene;
+pub mod text;
+pub mod tiled;
 
-pub enum EntityType {
-    Snake,
-    Food,
-    Gold,
-    Silver,
-    Rock,
-    Gem,
-    Claw,
-}
+pub use geom::Vec2;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Rect {
@@ -0,0 +1,251 @@
+use crate::actions::{parse_control, Control};
+use crate::input::Input;
+use std::path::Path;
+
+/// A recorded run: the seed the game's RNG started from, the timestep,
+/// and what was held on every tick.  Feeding the same input to the same
+/// seed through [`crate::app::Game::simulate`] gives the same run, so a
+/// replay file reproduces a bug report or pins down a regression test.
+/// Only controls with a name (see [`Control::name`]) are recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub dt: f32,
+    /// The controls held from each tick on, only where they changed
+    changes: Vec<(usize, Vec<Control>)>,
+    ticks: usize,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    /// The file is from a newer version of the format
+    Version(u32),
+    Missing {
+        what: &'static str,
+    },
+    BadLine {
+        line: usize,
+        text: String,
+    },
+    UnknownControl {
+        line: usize,
+        name: String,
+    },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "couldn't read replay: {e}"),
+            Self::Version(v) => write!(f, "replay version {v} is newer than {VERSION}"),
+            Self::Missing { what } => write!(f, "replay has no {what}"),
+            Self::BadLine { line, text } => write!(f, "line {line}: can't read {text:?}"),
+            Self::UnknownControl { line, name } => {
+                write!(f, "line {line}: unknown key {name:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+const VERSION: u32 = 1;
+
+fn number<T: std::str::FromStr>(s: Option<&str>) -> Option<T> {
+    s?.parse().ok()
+}
+
+impl Replay {
+    /// An empty recording for a run seeded with `seed`
+    pub fn new(seed: u64, dt: f32) -> Self {
+        Self {
+            seed,
+            dt,
+            changes: vec![],
+            ticks: 0,
+        }
+    }
+    /// How many ticks have been recorded
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+    /// Records the input the next tick is about to be simulated with
+    pub fn record(&mut self, input: &Input) {
+        let mut held: Vec<Control> = Control::all_down(input)
+            .filter(|c| c.name().is_some())
+            .collect();
+        held.sort_by_key(|c| c.name());
+        if self.changes.last().is_none_or(|(_, last)| *last != held) {
+            self.changes.push((self.ticks, held));
+        }
+        self.ticks += 1;
+    }
+    /// Sets `input` to what was held on tick `tick`.  Past the end of
+    /// the recording, the last state stays held.
+    pub fn apply(&self, tick: usize, input: &mut Input) {
+        let idx = self.changes.partition_point(|(t, _)| *t <= tick);
+        input.release_all();
+        let Some((_, held)) = idx.checked_sub(1).map(|i| &self.changes[i]) else {
+            return;
+        };
+        for control in held {
+            match *control {
+                Control::Key(key) => input.press(key),
+            }
+        }
+    }
+
+    /*
+    Replay files start with a header, then have one line per tick where
+    the held controls changed: the tick, then every control held from
+    then on.  Blank lines and lines starting with # are ignored:
+
+    version 1
+    seed 1234
+    dt 0.016666668
+    ticks 300
+    0
+    12 ArrowUp
+    14 ArrowLeft Space
+    */
+    pub fn parse(s: &str) -> Result<Self, ReplayError> {
+        let mut version = None;
+        let mut seed = None;
+        let mut dt = None;
+        let mut ticks = None;
+        let mut changes = vec![];
+        for (line_no, line) in s.lines().enumerate() {
+            let line_no = line_no + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = || ReplayError::BadLine {
+                line: line_no,
+                text: line.to_string(),
+            };
+            let mut fields = line.split_whitespace();
+            let first = fields.next().unwrap();
+            let value = fields.next();
+            match first {
+                "version" => version = Some(number(value).ok_or_else(bad)?),
+                "seed" => seed = Some(number(value).ok_or_else(bad)?),
+                "dt" => dt = Some(number(value).ok_or_else(bad)?),
+                "ticks" => ticks = Some(number(value).ok_or_else(bad)?),
+                tick => {
+                    let tick: usize = tick.parse().map_err(|_| bad())?;
+                    if changes.last().is_some_and(|(t, _)| *t >= tick) {
+                        return Err(bad());
+                    }
+                    let held = value
+                        .into_iter()
+                        .chain(fields)
+                        .map(|name| {
+                            parse_control(name).ok_or(ReplayError::UnknownControl {
+                                line: line_no,
+                                name: name.to_string(),
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    changes.push((tick, held));
+                }
+            }
+        }
+        let version: u32 = version.ok_or(ReplayError::Missing { what: "version" })?;
+        if version > VERSION {
+            return Err(ReplayError::Version(version));
+        }
+        Ok(Self {
+            seed: seed.ok_or(ReplayError::Missing { what: "seed" })?,
+            dt: dt.ok_or(ReplayError::Missing { what: "dt" })?,
+            changes,
+            ticks: ticks.ok_or(ReplayError::Missing { what: "ticks" })?,
+        })
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "version {VERSION}")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "dt {}", self.dt)?;
+        writeln!(f, "ticks {}", self.ticks)?;
+        for (tick, held) in &self.changes {
+            write!(f, "{tick}")?;
+            for control in held {
+                // unnamed controls were never recorded
+                write!(f, " {}", control.name().unwrap())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Key;
+
+    #[test]
+    fn test_record_and_parse() {
+        let mut replay = Replay::new(42, 1.0 / 60.0);
+        let mut input = Input::default();
+        replay.record(&input);
+        input.press(Key::ArrowUp);
+        replay.record(&input);
+        replay.record(&input);
+        input.press(Key::Space);
+        input.press(Key::Lang1);
+        replay.record(&input);
+        input.release_all();
+        replay.record(&input);
+        assert_eq!(replay.ticks(), 5);
+        let text = replay.to_string();
+        assert_eq!(
+            text.lines().skip(4).collect::<Vec<_>>(),
+            ["0", "1 ArrowUp", "3 ArrowUp Space", "4"]
+        );
+        let parsed = Replay::parse(&text).unwrap();
+        assert_eq!(parsed, replay);
+        // playing back restores what was held on each tick
+        let mut input = Input::default();
+        parsed.apply(2, &mut input);
+        assert!(input.is_key_down(Key::ArrowUp));
+        assert!(!input.is_key_down(Key::Space));
+        parsed.apply(3, &mut input);
+        assert!(input.is_key_down(Key::Space));
+        parsed.apply(99, &mut input);
+        assert!(!input.is_key_down(Key::ArrowUp));
+
+        assert!(matches!(
+            Replay::parse("version 2\nseed 1\ndt 0.1\nticks 0"),
+            Err(ReplayError::Version(2))
+        ));
+        assert!(matches!(
+            Replay::parse("version 1\ndt 0.1\nticks 0"),
+            Err(ReplayError::Missing { what: "seed" })
+        ));
+        assert!(matches!(
+            Replay::parse("version 1\nseed 1\ndt 0.1\nticks 2\n0 Kayak"),
+            Err(ReplayError::UnknownControl { line: 5, .. })
+        ));
+        assert!(matches!(
+            Replay::parse("version 1\nseed 1\ndt 0.1\nticks 2\n1\n0"),
+            Err(ReplayError::BadLine { line: 6, .. })
+        ));
+    }
+}
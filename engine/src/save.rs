@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Where a game keeps files between runs: a folder in the platform's
+/// data directory, or just memory for tests and the web.
+#[derive(Clone, Debug, Default)]
+pub struct Storage {
+    dir: Option<PathBuf>,
+    memory: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// The first line isn't a header for this kind of file and game
+    Header {
+        expected: String,
+        found: String,
+    },
+    /// Saved by a different version of the game
+    Version {
+        expected: u32,
+        found: u32,
+    },
+    Missing {
+        key: String,
+    },
+    BadValue {
+        key: String,
+        text: String,
+    },
+    BadLine {
+        line: usize,
+        text: String,
+    },
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Header { expected, found } => {
+                write!(f, "expected a {expected:?} file, found {found:?}")
+            }
+            Self::Version { expected, found } => {
+                write!(
+                    f,
+                    "saved as version {found}, but this is version {expected}"
+                )
+            }
+            Self::Missing { key } => write!(f, "no {key}"),
+            Self::BadValue { key, text } => write!(f, "can't read {key} from {text:?}"),
+            Self::BadLine { line, text } => write!(f, "line {line}: can't read {text:?}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl Storage {
+    /// Files for the game called `game`, under e.g. `~/.local/share` on
+    /// Linux.  Falls back to memory where there's no data directory.
+    pub fn in_data_dir(game: &str) -> Self {
+        Self {
+            dir: dirs::data_dir().map(|d| d.join(game)),
+            memory: HashMap::new(),
+        }
+    }
+    /// Files kept in memory only and gone when the game ends
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+    /// The file `name`, if it's there and readable
+    pub fn read(&self, name: &str) -> Option<String> {
+        match &self.dir {
+            Some(dir) => std::fs::read_to_string(dir.join(name)).ok(),
+            None => self.memory.get(name).cloned(),
+        }
+    }
+    pub fn write(&mut self, name: &str, contents: &str) -> std::io::Result<()> {
+        match &self.dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                // write then rename, so quitting mid-save can't leave half a file
+                let tmp = dir.join(format!("{name}.tmp"));
+                std::fs::write(&tmp, contents)?;
+                std::fs::rename(tmp, dir.join(name))
+            }
+            None => {
+                self.memory.insert(name.to_string(), contents.to_string());
+                Ok(())
+            }
+        }
+    }
+    pub fn remove(&mut self, name: &str) {
+        match &self.dir {
+            Some(dir) => {
+                let _ = std::fs::remove_file(dir.join(name));
+            }
+            None => {
+                self.memory.remove(name);
+            }
+        }
+    }
+    /// Moves an unreadable file out of the way, keeping it around as
+    /// `name.corrupt` in case someone wants to look at it.
+    fn set_aside(&mut self, name: &str, error: &SaveError) {
+        eprintln!("Ignoring {name}: {error}");
+        if let Some(contents) = self.read(name) {
+            let _ = self.write(&format!("{name}.corrupt"), &contents);
+        }
+        self.remove(name);
+    }
+
+    /// The game's saved snapshot, if it has one that this `version` of
+    /// the game can read.  Broken or outdated ones are set aside.
+    pub fn load_snapshot(&mut self, game: &str, version: u32) -> Option<Snapshot> {
+        let text = self.read(SNAPSHOT_FILE)?;
+        match Snapshot::parse(&text, game, version) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                self.set_aside(SNAPSHOT_FILE, &e);
+                None
+            }
+        }
+    }
+    pub fn save_snapshot(&mut self, snapshot: &Snapshot) -> std::io::Result<()> {
+        self.write(SNAPSHOT_FILE, &snapshot.to_string())
+    }
+    /// Forgets the saved snapshot, e.g. once that game is over
+    pub fn clear_snapshot(&mut self) {
+        self.remove(SNAPSHOT_FILE);
+    }
+    /// The saved high scores, or an empty table if there aren't any or
+    /// they can't be read.
+    pub fn load_high_scores(&mut self) -> HighScores {
+        let Some(text) = self.read(HIGH_SCORES_FILE) else {
+            return HighScores::default();
+        };
+        HighScores::parse(&text).unwrap_or_else(|e| {
+            self.set_aside(HIGH_SCORES_FILE, &e);
+            HighScores::default()
+        })
+    }
+    pub fn save_high_scores(&mut self, scores: &HighScores) -> std::io::Result<()> {
+        self.write(HIGH_SCORES_FILE, &scores.to_string())
+    }
+}
+
+const SNAPSHOT_FILE: &str = "snapshot.txt";
+const HIGH_SCORES_FILE: &str = "highscores.txt";
+
+/// A game's state written out as named fields, one per line.  Each game
+/// decides what goes in it, and bumps its `version` whenever that
+/// changes so old snapshots aren't misread.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    game: String,
+    version: u32,
+    fields: Vec<(String, String)>,
+}
+
+/*
+Snapshot files start with a header naming the game and the version of
+its snapshot, then have one field per line, the key and then its value:
+
+snapshot snake 1
+level 0
+dir right
+body 10 10 9 10 8 10
+*/
+impl Snapshot {
+    pub fn new(game: &str, version: u32) -> Self {
+        Self {
+            game: game.to_string(),
+            version,
+            fields: vec![],
+        }
+    }
+    /// Sets `key`, replacing any value it had
+    pub fn set(&mut self, key: &str, value: impl Display) {
+        let value = value.to_string();
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+    }
+    pub fn set_list<T: Display>(&mut self, key: &str, values: impl IntoIterator<Item = T>) {
+        let values: Vec<String> = values.into_iter().map(|v| v.to_string()).collect();
+        self.set(key, values.join(" "));
+    }
+    pub fn set_pairs<T: Display>(&mut self, key: &str, values: impl IntoIterator<Item = (T, T)>) {
+        self.set_list(key, values.into_iter().flat_map(|(a, b)| [a, b]));
+    }
+    pub fn has(&self, key: &str) -> bool {
+        self.fields.iter().any(|(k, _)| k == key)
+    }
+    fn text(&self, key: &str) -> Result<&str, SaveError> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .ok_or_else(|| SaveError::Missing {
+                key: key.to_string(),
+            })
+    }
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<T, SaveError> {
+        let text = self.text(key)?;
+        text.parse().map_err(|_| SaveError::BadValue {
+            key: key.to_string(),
+            text: text.to_string(),
+        })
+    }
+    pub fn get_list<T: FromStr>(&self, key: &str) -> Result<Vec<T>, SaveError> {
+        let text = self.text(key)?;
+        text.split_whitespace()
+            .map(|v| v.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| SaveError::BadValue {
+                key: key.to_string(),
+                text: text.to_string(),
+            })
+    }
+    pub fn get_pairs<T: FromStr>(&self, key: &str) -> Result<Vec<(T, T)>, SaveError> {
+        let mut values = self.get_list::<T>(key)?;
+        if values.len() % 2 != 0 {
+            return Err(SaveError::BadValue {
+                key: key.to_string(),
+                text: self.text(key)?.to_string(),
+            });
+        }
+        let mut pairs = vec![];
+        while !values.is_empty() {
+            let mut two = values.drain(..2);
+            pairs.push((two.next().unwrap(), two.next().unwrap()));
+        }
+        Ok(pairs)
+    }
+    /// Reads a snapshot of `game`, which must have been saved as `version`
+    pub fn parse(s: &str, game: &str, version: u32) -> Result<Self, SaveError> {
+        let mut lines = s.lines().enumerate();
+        let header = lines.next().map_or("", |(_, l)| l.trim());
+        let expected = format!("snapshot {game}");
+        let found = header
+            .strip_prefix(&expected)
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| SaveError::Header {
+                expected: expected.clone(),
+                found: header.to_string(),
+            })?;
+        if found != version {
+            return Err(SaveError::Version {
+                expected: version,
+                found,
+            });
+        }
+        let mut snapshot = Self::new(game, version);
+        for (line_no, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            if snapshot.has(key) {
+                return Err(SaveError::BadLine {
+                    line: line_no + 1,
+                    text: line.to_string(),
+                });
+            }
+            snapshot.set(key, value.trim());
+        }
+        Ok(snapshot)
+    }
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "snapshot {} {}", self.game, self.version)?;
+        for (key, value) in &self.fields {
+            writeln!(f, "{key} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The best scores on each level, best first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HighScores {
+    levels: Vec<(String, Vec<usize>)>,
+}
+
+/// How many scores each level keeps
+pub const HIGH_SCORES_KEPT: usize = 5;
+const HIGH_SCORES_VERSION: u32 = 1;
+
+/*
+High score files have a version header, then a line per level with its
+name and then its scores, best first:
+
+highscores 1
+level 120 80 40
+level2 300
+*/
+impl HighScores {
+    /// Adds `score` to the table for `level` if it's good enough.
+    /// Returns where it placed, counting from 0 for the best.
+    pub fn insert(&mut self, level: &str, score: usize) -> Option<usize> {
+        let idx = match self.levels.iter().position(|(name, _)| name == level) {
+            Some(idx) => idx,
+            None => {
+                self.levels.push((level.to_string(), vec![]));
+                self.levels.len() - 1
+            }
+        };
+        let scores = &mut self.levels[idx].1;
+        let rank = scores.partition_point(|s| *s >= score);
+        if rank >= HIGH_SCORES_KEPT {
+            return None;
+        }
+        scores.insert(rank, score);
+        scores.truncate(HIGH_SCORES_KEPT);
+        Some(rank)
+    }
+    /// Every score kept for `level`, best first
+    pub fn scores(&self, level: &str) -> &[usize] {
+        self.levels
+            .iter()
+            .find(|(name, _)| name == level)
+            .map_or(&[], |(_, scores)| scores.as_slice())
+    }
+    pub fn best(&self, level: &str) -> Option<usize> {
+        self.scores(level).first().copied()
+    }
+    pub fn parse(s: &str) -> Result<Self, SaveError> {
+        let mut lines = s.lines().enumerate();
+        let header = lines.next().map_or("", |(_, l)| l.trim());
+        let found = header
+            .strip_prefix("highscores")
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| SaveError::Header {
+                expected: "highscores".to_string(),
+                found: header.to_string(),
+            })?;
+        if found != HIGH_SCORES_VERSION {
+            return Err(SaveError::Version {
+                expected: HIGH_SCORES_VERSION,
+                found,
+            });
+        }
+        let mut table = Self::default();
+        for (line_no, line) in lines {
+            let bad = || SaveError::BadLine {
+                line: line_no + 1,
+                text: line.to_string(),
+            };
+            let mut fields = line.split_whitespace();
+            let Some(level) = fields.next() else {
+                continue;
+            };
+            let scores: Vec<usize> = fields
+                .map(|s| s.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| bad())?;
+            let sorted = scores.windows(2).all(|w| w[0] >= w[1]);
+            if !sorted || scores.len() > HIGH_SCORES_KEPT || !table.scores(level).is_empty() {
+                return Err(bad());
+            }
+            table.levels.push((level.to_string(), scores));
+        }
+        Ok(table)
+    }
+}
+
+impl std::fmt::Display for HighScores {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "highscores {HIGH_SCORES_VERSION}")?;
+        for (level, scores) in &self.levels {
+            write!(f, "{level}")?;
+            for score in scores {
+                write!(f, " {score}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let mut snap = Snapshot::new("snake", 2);
+        snap.set("level", 1);
+        snap.set_pairs("body", [(10, 10), (9, 10)]);
+        snap.set_list("empty", Vec::<f32>::new());
+        snap.set("level", 3);
+        let text = snap.to_string();
+        assert_eq!(text, "snapshot snake 2\nlevel 3\nbody 10 10 9 10\nempty \n");
+        let read = Snapshot::parse(&text, "snake", 2).unwrap();
+        assert_eq!(read, snap);
+        assert_eq!(read.get::<usize>("level"), Ok(3));
+        assert_eq!(read.get_pairs::<usize>("body"), Ok(vec![(10, 10), (9, 10)]));
+        assert_eq!(read.get_list::<f32>("empty"), Ok(vec![]));
+        assert!(matches!(
+            read.get::<usize>("dir"),
+            Err(SaveError::Missing { .. })
+        ));
+        assert!(matches!(
+            read.get::<bool>("level"),
+            Err(SaveError::BadValue { .. })
+        ));
+        assert_eq!(
+            Snapshot::parse(&text, "snake", 3),
+            Err(SaveError::Version {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert!(matches!(
+            Snapshot::parse(&text, "tron", 2),
+            Err(SaveError::Header { .. })
+        ));
+        assert!(matches!(
+            Snapshot::parse("\u{0}garbage", "snake", 2),
+            Err(SaveError::Header { .. })
+        ));
+    }
+    #[test]
+    fn test_high_scores() {
+        let mut scores = HighScores::default();
+        assert_eq!(scores.best("level"), None);
+        for (score, rank) in [(40, 0), (120, 0), (80, 1), (80, 2), (10, 4), (5, 5)] {
+            let expected = (rank < HIGH_SCORES_KEPT).then_some(rank);
+            assert_eq!(scores.insert("level", score), expected, "{score}");
+        }
+        scores.insert("level2", 300);
+        assert_eq!(scores.scores("level"), [120, 80, 80, 40, 10]);
+        // a sixth score has to beat the worst one
+        assert_eq!(scores.insert("level", 20), Some(4));
+        assert_eq!(scores.scores("level"), [120, 80, 80, 40, 20]);
+        let text = scores.to_string();
+        assert_eq!(text, "highscores 1\nlevel 120 80 80 40 20\nlevel2 300\n");
+        assert_eq!(HighScores::parse(&text), Ok(scores));
+        assert!(HighScores::parse("highscores 1\nlevel 1 2\n").is_err());
+        assert!(HighScores::parse("level 1\n").is_err());
+    }
+    #[test]
+    fn test_corrupt_files_are_set_aside() {
+        let mut storage = Storage::in_memory();
+        storage
+            .write(HIGH_SCORES_FILE, "highscores 1\nlevel ten\n")
+            .unwrap();
+        assert_eq!(storage.load_high_scores(), HighScores::default());
+        assert_eq!(storage.read(HIGH_SCORES_FILE), None);
+        assert!(storage.read("highscores.txt.corrupt").is_some());
+        storage
+            .write(SNAPSHOT_FILE, "snapshot snake 1\nlevel 2\n")
+            .unwrap();
+        assert_eq!(storage.load_snapshot("snake", 2), None);
+        assert_eq!(storage.read(SNAPSHOT_FILE), None);
+        let snap = Snapshot::new("snake", 2);
+        storage.save_snapshot(&snap).unwrap();
+        assert_eq!(storage.load_snapshot("snake", 2), Some(snap));
+    }
+}
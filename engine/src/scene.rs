@@ -0,0 +1,225 @@
+use crate::app::SpriteSink;
+use crate::input::Input;
+
+/// What a [`Scene`] wants the stack to do after it simulates.
+#[derive(Debug, PartialEq)]
+pub enum Transition<S> {
+    Stay,
+    /// Put a scene on top of this one, e.g. a pause menu
+    Push(S),
+    /// Go back to the scene underneath
+    Pop,
+    /// Swap this scene for another, e.g. title screen to gameplay
+    Replace(S),
+}
+
+/// One screen of a game, like a title screen or a pause menu.  Games
+/// usually implement this on an enum of their screens, with the game
+/// itself as the `Context` that every scene works on.
+pub trait Scene: Sized {
+    type Context;
+    /// Called when the scene goes on the stack
+    fn enter(&mut self, _ctx: &mut Self::Context) {}
+    /// Called when the scene comes off the stack
+    fn exit(&mut self, _ctx: &mut Self::Context) {}
+    /// Only the top scene is simulated.
+    fn simulate(&mut self, ctx: &mut Self::Context, input: &Input, dt: f32) -> Transition<Self>;
+    fn render(&self, ctx: &Self::Context, frend: &mut impl SpriteSink);
+    /// Whether the scene underneath should still be drawn first, as
+    /// for a pause screen drawn over the paused game
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+/// A stack of scenes where the top one is running.
+#[derive(Debug)]
+pub struct SceneStack<S> {
+    scenes: Vec<S>,
+}
+
+impl<S> Default for SceneStack<S> {
+    fn default() -> Self {
+        Self { scenes: vec![] }
+    }
+}
+
+impl<S: Scene> SceneStack<S> {
+    /// A stack holding just `first`, which is entered right away.
+    pub fn new(first: S, ctx: &mut S::Context) -> Self {
+        let mut stack = Self::default();
+        stack.push(first, ctx);
+        stack
+    }
+    pub fn top(&self) -> Option<&S> {
+        self.scenes.last()
+    }
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+    pub fn push(&mut self, mut scene: S, ctx: &mut S::Context) {
+        scene.enter(ctx);
+        self.scenes.push(scene);
+    }
+    pub fn pop(&mut self, ctx: &mut S::Context) -> Option<S> {
+        let mut scene = self.scenes.pop()?;
+        scene.exit(ctx);
+        Some(scene)
+    }
+    /// Exits the top scene (if any) and enters `scene` in its place.
+    pub fn replace(&mut self, scene: S, ctx: &mut S::Context) -> Option<S> {
+        let old = self.pop(ctx);
+        self.push(scene, ctx);
+        old
+    }
+    pub fn apply(&mut self, transition: Transition<S>, ctx: &mut S::Context) {
+        match transition {
+            Transition::Stay => {}
+            Transition::Push(scene) => self.push(scene, ctx),
+            Transition::Pop => {
+                self.pop(ctx);
+            }
+            Transition::Replace(scene) => {
+                self.replace(scene, ctx);
+            }
+        }
+    }
+    /// Simulates the top scene and carries out whatever transition it asks for.
+    pub fn simulate(&mut self, ctx: &mut S::Context, input: &Input, dt: f32) {
+        if let Some(top) = self.scenes.last_mut() {
+            let transition = top.simulate(ctx, input, dt);
+            self.apply(transition, ctx);
+        }
+    }
+    /// Renders the top scene, after whichever scenes under it show
+    /// through its overlays.
+    pub fn render(&self, ctx: &S::Context, frend: &mut impl SpriteSink) {
+        let first_visible = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        for scene in &self.scenes[first_visible..] {
+            scene.render(ctx, frend);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::RecordingSink;
+    use crate::input::Key;
+    use frenderer::sprites::{SheetRegion, Transform};
+
+    #[derive(Debug, PartialEq)]
+    enum Screen {
+        Title,
+        Playing,
+        Paused,
+    }
+    #[derive(Default)]
+    struct Log {
+        events: Vec<String>,
+        ticks: usize,
+    }
+    impl Scene for Screen {
+        type Context = Log;
+        fn enter(&mut self, log: &mut Log) {
+            log.events.push(format!("enter {self:?}"));
+        }
+        fn exit(&mut self, log: &mut Log) {
+            log.events.push(format!("exit {self:?}"));
+        }
+        fn simulate(&mut self, log: &mut Log, input: &Input, _dt: f32) -> Transition<Self> {
+            match self {
+                Screen::Title if input.is_key_pressed(Key::Space) => {
+                    Transition::Replace(Screen::Playing)
+                }
+                Screen::Playing if input.is_key_pressed(Key::Escape) => {
+                    Transition::Push(Screen::Paused)
+                }
+                Screen::Paused if input.is_key_pressed(Key::Escape) => Transition::Pop,
+                Screen::Playing => {
+                    log.ticks += 1;
+                    Transition::Stay
+                }
+                _ => Transition::Stay,
+            }
+        }
+        fn render(&self, _log: &Log, frend: &mut impl SpriteSink) {
+            let group = match self {
+                Screen::Title => 0,
+                Screen::Playing => 1,
+                Screen::Paused => 2,
+            };
+            frend.draw_sprite(group, Transform::ZERO, SheetRegion::ZERO);
+        }
+        fn is_overlay(&self) -> bool {
+            *self == Screen::Paused
+        }
+    }
+
+    fn tap(stack: &mut SceneStack<Screen>, log: &mut Log, key: Key) {
+        let mut input = Input::default();
+        input.press(key);
+        stack.simulate(log, &input, 1.0);
+    }
+
+    #[test]
+    fn test_transitions_and_hooks() {
+        let mut log = Log::default();
+        let mut stack = SceneStack::new(Screen::Title, &mut log);
+        tap(&mut stack, &mut log, Key::Space);
+        assert_eq!(stack.top(), Some(&Screen::Playing));
+        stack.simulate(&mut log, &Input::default(), 1.0);
+        tap(&mut stack, &mut log, Key::Escape);
+        assert_eq!(stack.top(), Some(&Screen::Paused));
+        assert_eq!(stack.len(), 2);
+        // only the top scene runs
+        stack.simulate(&mut log, &Input::default(), 1.0);
+        assert_eq!(log.ticks, 1);
+        tap(&mut stack, &mut log, Key::Escape);
+        assert_eq!(stack.top(), Some(&Screen::Playing));
+        assert_eq!(
+            log.events,
+            [
+                "enter Title",
+                "exit Title",
+                "enter Playing",
+                "enter Paused",
+                "exit Paused"
+            ]
+        );
+        assert_eq!(stack.pop(&mut log), Some(Screen::Playing));
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(&mut log), None);
+    }
+    #[test]
+    fn test_overlays_render_over_scene_below() {
+        let mut log = Log::default();
+        let mut stack = SceneStack::new(Screen::Title, &mut log);
+        stack.push(Screen::Playing, &mut log);
+        let mut sink = RecordingSink::default();
+        stack.render(&log, &mut sink);
+        assert_eq!(
+            (sink.sprite_count(0), sink.sprite_count(1)),
+            (0, 1),
+            "opaque scenes hide the ones below"
+        );
+        stack.push(Screen::Paused, &mut log);
+        sink.clear();
+        stack.render(&log, &mut sink);
+        assert_eq!(
+            (
+                sink.sprite_count(0),
+                sink.sprite_count(1),
+                sink.sprite_count(2)
+            ),
+            (0, 1, 1)
+        );
+    }
+}
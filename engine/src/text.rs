@@ -0,0 +1,415 @@
+use crate::app::SpriteSink;
+use crate::Vec2;
+use frenderer::sprites::{SheetRegion, Transform};
+use std::collections::HashMap;
+
+/// One character of a [`Font`].  Sizes and offsets are in font
+/// pixels, i.e. world units when drawn at scale 1.
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+    pub region: SheetRegion,
+    /// From the pen position to the glyph's top-left corner, with y
+    /// pointing down like in the spritesheet
+    pub offset: Vec2,
+    /// How far to move the pen after drawing this glyph
+    pub advance: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FontParseError {
+    /// A required key is missing from a line
+    Missing { line: usize, what: &'static str },
+    BadNumber {
+        line: usize,
+        what: &'static str,
+        text: String,
+    },
+    /// The descriptor has no `common` line with the line height
+    NoLineHeight,
+}
+
+impl std::fmt::Display for FontParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { line, what } => write!(f, "line {line}: no {what}"),
+            Self::BadNumber { line, what, text } => {
+                write!(f, "line {line}: couldn't parse {what} from {text:?}")
+            }
+            Self::NoLineHeight => write!(f, "font has no common line with a lineHeight"),
+        }
+    }
+}
+
+impl std::error::Error for FontParseError {}
+
+/// A bitmap font: glyphs cut out of a spritesheet, laid out one line
+/// at a time.  Characters the font doesn't have fall back to their
+/// uppercase version, then to a blank space.
+#[derive(Clone, Debug)]
+pub struct Font {
+    glyphs: HashMap<char, Glyph>,
+    line_height: f32,
+}
+
+impl Font {
+    /// A fixed-width font whose glyphs fill `region` in rows of
+    /// `glyph_w` by `glyph_h` cells, left to right and top to bottom,
+    /// in the order `chars` lists them.  Any spacing between glyphs
+    /// has to be part of the cells.
+    pub fn fixed_width(
+        region: SheetRegion,
+        chars: impl IntoIterator<Item = char>,
+        glyph_w: u16,
+        glyph_h: u16,
+    ) -> Self {
+        assert!(glyph_w > 0 && glyph_h > 0, "Glyphs must have a size");
+        let columns = region.w as u16 / glyph_w;
+        assert!(columns > 0, "Font region is narrower than a glyph");
+        let glyphs: HashMap<char, Glyph> = chars
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let (col, row) = (i as u16 % columns, i as u16 / columns);
+                assert!(
+                    (row + 1) * glyph_h <= region.h as u16,
+                    "Font region is too small for its characters"
+                );
+                let glyph = Glyph {
+                    region: SheetRegion::new(
+                        region.sheet,
+                        region.x + col * glyph_w,
+                        region.y + row * glyph_h,
+                        region.depth,
+                        glyph_w as i16,
+                        glyph_h as i16,
+                    ),
+                    offset: Vec2::ZERO,
+                    advance: glyph_w as f32,
+                };
+                (c, glyph)
+            })
+            .collect();
+        Self {
+            glyphs,
+            line_height: glyph_h as f32,
+        }
+    }
+    /*
+    Parses the text flavor of AngelCode's BMFont descriptors.  Only the
+    lineHeight of the common line and the char lines are used, so
+    other lines (info, page, kernings...) are skipped:
+
+    common lineHeight=8 base=7 scaleW=64 scaleH=64 pages=1
+    char id=65 x=0 y=0 width=5 height=7 xoffset=0 yoffset=1 xadvance=6 page=0 chnl=15
+
+    Glyph coordinates are relative to `origin`, so the font's page can
+    live anywhere on a bigger spritesheet.  The sheet and depth of
+    `origin` are used for every glyph.
+    */
+    pub fn parse_bmfont(s: &str, origin: SheetRegion) -> Result<Self, FontParseError> {
+        let mut glyphs = HashMap::new();
+        let mut line_height = None;
+        for (line_no, line) in s.lines().enumerate() {
+            let line_no = line_no + 1;
+            let mut words = line.split_whitespace();
+            let tag = words.next();
+            if tag != Some("common") && tag != Some("char") {
+                continue;
+            }
+            let pairs: Vec<(&str, &str)> = words.filter_map(|word| word.split_once('=')).collect();
+            let number = |key: &'static str| -> Result<i32, FontParseError> {
+                let (_, text) =
+                    pairs
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .ok_or(FontParseError::Missing {
+                            line: line_no,
+                            what: key,
+                        })?;
+                text.parse().map_err(|_| FontParseError::BadNumber {
+                    line: line_no,
+                    what: key,
+                    text: text.to_string(),
+                })
+            };
+            if tag == Some("common") {
+                line_height = Some(number("lineHeight")? as f32);
+                continue;
+            }
+            let id = number("id")?;
+            let Some(c) = char::from_u32(id as u32) else {
+                return Err(FontParseError::BadNumber {
+                    line: line_no,
+                    what: "id",
+                    text: id.to_string(),
+                });
+            };
+            glyphs.insert(
+                c,
+                Glyph {
+                    region: SheetRegion::new(
+                        origin.sheet,
+                        origin.x + number("x")? as u16,
+                        origin.y + number("y")? as u16,
+                        origin.depth,
+                        number("width")? as i16,
+                        number("height")? as i16,
+                    ),
+                    offset: Vec2::new(number("xoffset")? as f32, number("yoffset")? as f32),
+                    advance: number("xadvance")? as f32,
+                },
+            );
+        }
+        Ok(Self {
+            glyphs,
+            line_height: line_height.ok_or(FontParseError::NoLineHeight)?,
+        })
+    }
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+    /// The glyph drawn for `c`, if any
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| c.to_uppercase().next().and_then(|up| self.glyphs.get(&up)))
+    }
+    /// How far the pen moves for `c`; unknown characters are as wide
+    /// as a space, or as the font is tall if there's no space either.
+    fn advance(&self, c: char) -> f32 {
+        self.glyph(c)
+            .or_else(|| self.glyphs.get(&' '))
+            .map_or(self.line_height, |g| g.advance)
+    }
+    /// Width and height of `text` drawn at `scale`, in world units.
+    /// Each `\n` starts a new line.
+    pub fn measure(&self, text: &str, scale: f32) -> Vec2 {
+        let width = text
+            .lines()
+            .map(|line| line.chars().map(|c| self.advance(c)).sum::<f32>())
+            .fold(0.0, f32::max);
+        let lines = text.lines().count().max(1);
+        Vec2::new(width, lines as f32 * self.line_height) * scale
+    }
+    /// Lays `text` out at `scale` as sprite transforms and sheet regions.
+    /// `pos` is the top of the first line, at its left edge, center or
+    /// right edge depending on `align`; every line is aligned the same way.
+    pub fn layout(
+        &self,
+        text: &str,
+        pos: Vec2,
+        align: Align,
+        scale: f32,
+    ) -> Vec<(Transform, SheetRegion)> {
+        let mut sprites = vec![];
+        for (line_no, line) in text.lines().enumerate() {
+            let width = self.measure(line, scale).x;
+            let mut pen = Vec2::new(
+                match align {
+                    Align::Left => pos.x,
+                    Align::Center => pos.x - width / 2.0,
+                    Align::Right => pos.x - width,
+                },
+                pos.y - line_no as f32 * self.line_height * scale,
+            );
+            for c in line.chars() {
+                // whitespace and empty glyphs just move the pen
+                let glyph = self.glyph(c).filter(|g| g.region.w != 0);
+                if let Some(glyph) = glyph.filter(|_| !c.is_whitespace()) {
+                    let w = glyph.region.w.unsigned_abs() as f32 * scale;
+                    let h = glyph.region.h.unsigned_abs() as f32 * scale;
+                    sprites.push((
+                        Transform {
+                            x: pen.x + glyph.offset.x * scale + w / 2.0,
+                            y: pen.y - glyph.offset.y * scale - h / 2.0,
+                            w: w as u16,
+                            h: h as u16,
+                            rot: 0.0,
+                        },
+                        glyph.region,
+                    ));
+                }
+                pen.x += self.advance(c) * scale;
+            }
+        }
+        sprites
+    }
+    /// Draws `text` into sprite group `group`, laid out as by
+    /// [`Font::layout`].  Returns how many sprites it used.
+    pub fn draw(
+        &self,
+        frend: &mut impl SpriteSink,
+        group: usize,
+        text: &str,
+        pos: Vec2,
+        align: Align,
+        scale: f32,
+    ) -> usize {
+        let sprites = self.layout(text, pos, align, scale);
+        let (trfs, uvs) = frend.draw_sprites(group, sprites.len());
+        for ((trf, uv), (sprite_trf, sprite_uv)) in
+            trfs.iter_mut().zip(uvs.iter_mut()).zip(sprites.iter())
+        {
+            *trf = *sprite_trf;
+            *uv = *sprite_uv;
+        }
+        sprites.len()
+    }
+    /// Draws `n` in decimal, padded with zeroes to at least `digits` digits.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_number(
+        &self,
+        frend: &mut impl SpriteSink,
+        group: usize,
+        n: i64,
+        digits: usize,
+        pos: Vec2,
+        align: Align,
+        scale: f32,
+    ) -> usize {
+        let text = if n < 0 {
+            format!("-{:0digits$}", n.unsigned_abs())
+        } else {
+            format!("{n:0digits$}")
+        };
+        self.draw(frend, group, &text, pos, align, scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::RecordingSink;
+
+    // SheetRegion isn't PartialEq
+    fn rect(r: SheetRegion) -> (u16, u16, u16, u16, i16, i16) {
+        (r.sheet, r.depth, r.x, r.y, r.w, r.h)
+    }
+
+    // 4x6 cells, 16 to a row, like the games' font.png
+    fn grid_font() -> Font {
+        Font::fixed_width(SheetRegion::rect(0, 8, 64, 24), ' '..='Z', 4, 6)
+    }
+
+    #[test]
+    fn test_fixed_width() {
+        let font = grid_font();
+        assert_eq!(font.line_height(), 6.0);
+        assert_eq!(
+            rect(font.glyph('!').unwrap().region),
+            rect(SheetRegion::rect(4, 8, 4, 6))
+        );
+        // 'A' is character 33, the second one on the third row
+        assert_eq!(
+            rect(font.glyph('A').unwrap().region),
+            rect(SheetRegion::rect(4, 20, 4, 6))
+        );
+        assert!(std::ptr::eq(
+            font.glyph('a').unwrap(),
+            font.glyph('A').unwrap()
+        ));
+        assert!(font.glyph('~').is_none());
+        assert_eq!(font.measure("SCORE 10", 1.0), Vec2::new(32.0, 6.0));
+        assert_eq!(font.measure("AB\nCDE\n", 2.0), Vec2::new(24.0, 24.0));
+        assert_eq!(font.measure("", 1.0), Vec2::new(0.0, 6.0));
+    }
+    #[test]
+    fn test_layout_alignment() {
+        let font = grid_font();
+        let left = font.layout("HI", Vec2::new(10.0, 50.0), Align::Left, 1.0);
+        assert_eq!(left.len(), 2);
+        assert_eq!((left[0].0.x, left[0].0.y), (12.0, 47.0));
+        assert_eq!((left[1].0.x, left[1].0.y), (16.0, 47.0));
+        assert_eq!((left[0].0.w, left[0].0.h), (4, 6));
+        assert_eq!(rect(left[0].1), rect(font.glyph('H').unwrap().region));
+        let right = font.layout("HI", Vec2::new(10.0, 50.0), Align::Right, 1.0);
+        assert_eq!(right[1].0.x, 8.0);
+        let center = font.layout("HI", Vec2::new(10.0, 50.0), Align::Center, 2.0);
+        assert_eq!((center[0].0.x, center[0].0.y), (6.0, 44.0));
+        assert_eq!((center[0].0.w, center[0].0.h), (8, 12));
+        // spaces take room but no sprites, and each line is aligned on its own
+        let lines = font.layout("A B\nC", Vec2::new(0.0, 0.0), Align::Center, 1.0);
+        assert_eq!(lines.len(), 3);
+        // "A B" is 12 wide, so B starts at -6 + 8
+        assert_eq!(lines[1].0.x, 2.0 + 2.0);
+        assert_eq!((lines[2].0.x, lines[2].0.y), (0.0, -9.0));
+    }
+    #[test]
+    fn test_draw_into_sink() {
+        let font = grid_font();
+        let mut sink = RecordingSink::default();
+        assert_eq!(
+            font.draw(&mut sink, 1, "GO", Vec2::ZERO, Align::Left, 1.0),
+            2
+        );
+        assert_eq!(
+            font.draw_number(&mut sink, 1, 7, 3, Vec2::ZERO, Align::Left, 1.0),
+            3
+        );
+        assert_eq!(
+            font.draw_number(&mut sink, 1, -42, 0, Vec2::ZERO, Align::Left, 1.0),
+            3
+        );
+        assert_eq!(sink.sprite_count(1), 8);
+        assert_eq!(
+            rect(sink.sheet_regions(1)[2]),
+            rect(font.glyph('0').unwrap().region)
+        );
+        assert_eq!(
+            rect(sink.sheet_regions(1)[4]),
+            rect(font.glyph('7').unwrap().region)
+        );
+        assert_eq!(
+            rect(sink.sheet_regions(1)[5]),
+            rect(font.glyph('-').unwrap().region)
+        );
+    }
+    #[test]
+    fn test_bmfont() {
+        let font = Font::parse_bmfont(
+            "info face=\"Tiny\" size=8\n\
+             common lineHeight=9 base=7 scaleW=32 scaleH=32 pages=1\n\
+             page id=0 file=\"tiny.png\"\n\
+             chars count=2\n\
+             char id=65 x=0 y=0 width=5 height=7 xoffset=0 yoffset=1 xadvance=6 page=0\n\
+             char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=3 page=0\n",
+            SheetRegion::new(1, 100, 200, 5, 0, 0),
+        )
+        .unwrap();
+        assert_eq!(font.line_height(), 9.0);
+        let a = font.glyph('A').unwrap();
+        assert_eq!(rect(a.region), rect(SheetRegion::new(1, 100, 200, 5, 5, 7)));
+        assert_eq!(font.measure("A A", 1.0), Vec2::new(15.0, 9.0));
+        let sprites = font.layout("A A", Vec2::ZERO, Align::Left, 1.0);
+        assert_eq!(sprites.len(), 2);
+        assert_eq!((sprites[1].0.x, sprites[1].0.y), (11.5, -4.5));
+        assert_eq!(
+            Font::parse_bmfont("char id=65 x=0\n", SheetRegion::ZERO).unwrap_err(),
+            FontParseError::Missing { line: 1, what: "y" }
+        );
+        assert_eq!(
+            Font::parse_bmfont(
+                "char id=65 x=0 y=0 width=5 height=7 xoffset=0 yoffset=1 xadvance=six\n",
+                SheetRegion::ZERO
+            )
+            .unwrap_err(),
+            FontParseError::BadNumber {
+                line: 1,
+                what: "xadvance",
+                text: "six".to_string()
+            }
+        );
+        assert_eq!(
+            Font::parse_bmfont("", SheetRegion::ZERO).unwrap_err(),
+            FontParseError::NoLineHeight
+        );
+    }
+}
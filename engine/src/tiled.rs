@@ -0,0 +1,780 @@
+use crate::grid::Grid;
+use crate::level::{Layer, Level, DEFAULT_LAYER_DEPTH};
+use crate::{TileData, Vec2};
+use frenderer::sprites::SheetRegion;
+use quick_xml::events::{BytesStart, Event};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/*
+Maps made in the Tiled editor (https://www.mapeditor.org), saved as TMX
+(XML) or TMJ (JSON), can be loaded as levels:
+
+- Each tile layer becomes a level layer with the same name, drawn in the
+  same order.  A layer's `depth` (int) and `collides` (bool) custom
+  properties set its depth and whether it collides; they default to 16
+  and true.  Hidden layers stay hidden.
+- Each tile is the region of its tileset's image it comes from, on sheet
+  N for the Nth tileset in the map.  Tiles with a custom `solid` (bool)
+  property set to true are solid.  Empty cells draw nothing.
+- Each object in an object layer becomes a start, with its entity type
+  taken from the object's class (or type, before Tiled 1.9), or else its
+  name.  It starts in the middle of the cell its center is in.
+- The map's `name` custom property names the level.
+
+Tile layer data has to be CSV (or plain XML in TMX), not base64, and
+tilesets have to be embedded in the map rather than external files,
+and cut from one image rather than image collections.
+Flipped and rotated tiles are drawn unflipped.
+*/
+
+/// A Tiled map asset, loaded from a `.tmx` or `.tmj` file
+pub struct TiledMap(pub String);
+
+impl From<String> for TiledMap {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl assets_manager::Asset for TiledMap {
+    const EXTENSIONS: &'static [&'static str] = &["tmx", "tmj"];
+    type Loader = assets_manager::loader::LoadFrom<String, assets_manager::loader::StringLoader>;
+}
+
+#[derive(Debug)]
+pub enum TiledError {
+    Xml(quick_xml::Error),
+    Json(serde_json::Error),
+    Missing {
+        what: &'static str,
+    },
+    BadValue {
+        what: &'static str,
+        text: String,
+    },
+    /// Something Tiled can save that we can't import
+    Unsupported {
+        what: &'static str,
+    },
+    /// A tile that no tileset in the map covers
+    NoTileset {
+        gid: u32,
+    },
+    /// A layer using more than 256 different tiles
+    TooManyTiles {
+        layer: String,
+    },
+    UnknownEntity {
+        name: String,
+    },
+    /// An object outside the map
+    OutOfBounds {
+        name: String,
+    },
+}
+
+impl std::fmt::Display for TiledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Xml(e) => write!(f, "couldn't read TMX: {e}"),
+            Self::Json(e) => write!(f, "couldn't read TMJ: {e}"),
+            Self::Missing { what } => write!(f, "map has no {what}"),
+            Self::BadValue { what, text } => write!(f, "couldn't parse {what} from {text:?}"),
+            Self::Unsupported { what } => write!(f, "{what} aren't supported"),
+            Self::NoTileset { gid } => write!(f, "no tileset has tile {gid}"),
+            Self::TooManyTiles { layer } => {
+                write!(f, "layer {layer:?} uses more than 256 different tiles")
+            }
+            Self::UnknownEntity { name } => write!(f, "unrecognized entity type {name:?}"),
+            Self::OutOfBounds { name } => write!(f, "object {name:?} is outside the map"),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+impl From<quick_xml::Error> for TiledError {
+    fn from(e: quick_xml::Error) -> Self {
+        Self::Xml(e)
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for TiledError {
+    fn from(e: quick_xml::events::attributes::AttrError) -> Self {
+        Self::Xml(e.into())
+    }
+}
+
+impl From<serde_json::Error> for TiledError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// The top bits of a gid say how the tile is flipped
+const FLIP_BITS: u32 = 0xF000_0000;
+
+fn number<T: FromStr>(what: &'static str, text: &str) -> Result<T, TiledError> {
+    text.trim().parse().map_err(|_| TiledError::BadValue {
+        what,
+        text: text.to_string(),
+    })
+}
+
+fn parse_csv(text: &str) -> Result<Vec<u32>, TiledError> {
+    text.split(',')
+        .filter(|gid| !gid.trim().is_empty())
+        .map(|gid| number("tile gid", gid))
+        .collect()
+}
+
+type Properties = Vec<(String, String)>;
+
+fn property<'a>(props: &'a Properties, name: &str) -> Option<&'a str> {
+    props
+        .iter()
+        .find(|(prop, _)| prop == name)
+        .map(|(_, value)| value.as_str())
+}
+
+struct Tileset {
+    first_gid: u32,
+    columns: u32,
+    tile_w: u16,
+    tile_h: u16,
+    margin: u16,
+    spacing: u16,
+    /// Local ids of the solid tiles
+    solid: Vec<u32>,
+}
+
+struct TileLayer {
+    name: String,
+    visible: bool,
+    props: Properties,
+    data: Vec<u32>,
+}
+
+struct Object {
+    entity: String,
+    /// The center, in pixels from the top left of the map
+    center: (f32, f32),
+}
+
+/// A map as Tiled describes it, whichever format it came from
+#[derive(Default)]
+struct Map {
+    width: usize,
+    height: usize,
+    tile_w: usize,
+    tile_h: usize,
+    props: Properties,
+    tilesets: Vec<Tileset>,
+    layers: Vec<TileLayer>,
+    objects: Vec<Object>,
+}
+
+impl Map {
+    fn tile(&self, gid: u32, depth: u16) -> Result<TileData, TiledError> {
+        let gid = gid & !FLIP_BITS;
+        if gid == 0 {
+            return Ok(TileData {
+                solid: false,
+                sheet_region: SheetRegion::new(0, 0, 0, depth, 0, 0),
+            });
+        }
+        let (sheet, tileset) = self
+            .tilesets
+            .iter()
+            .enumerate()
+            .filter(|(_, ts)| ts.first_gid <= gid)
+            .max_by_key(|(_, ts)| ts.first_gid)
+            .ok_or(TiledError::NoTileset { gid })?;
+        let id = gid - tileset.first_gid;
+        let (col, row) = (id % tileset.columns, id / tileset.columns);
+        let (w, h) = (tileset.tile_w, tileset.tile_h);
+        Ok(TileData {
+            solid: tileset.solid.contains(&id),
+            sheet_region: SheetRegion::new(
+                sheet as u16,
+                tileset.margin + col as u16 * (w + tileset.spacing),
+                tileset.margin + row as u16 * (h + tileset.spacing),
+                depth,
+                w as i16,
+                h as i16,
+            ),
+        })
+    }
+
+    fn into_level<E: FromStr>(self) -> Result<Level<E>, TiledError> {
+        if self.tile_w == 0 || self.tile_h == 0 {
+            return Err(TiledError::BadValue {
+                what: "tile size",
+                text: format!("{}x{}", self.tile_w, self.tile_h),
+            });
+        }
+        if self.tile_w != self.tile_h {
+            return Err(TiledError::Unsupported {
+                what: "non-square tiles",
+            });
+        }
+        if self.layers.is_empty() {
+            return Err(TiledError::Missing {
+                what: "tile layers",
+            });
+        }
+        let (w, h, tile_sz) = (self.width, self.height, self.tile_w);
+        let mut layers = vec![];
+        for layer in &self.layers {
+            if layer.data.len() != w * h {
+                return Err(TiledError::BadValue {
+                    what: "layer data",
+                    text: format!("{} tiles", layer.data.len()),
+                });
+            }
+            let depth = property(&layer.props, "depth")
+                .map(|d| number("layer depth", d))
+                .transpose()?
+                .unwrap_or(DEFAULT_LAYER_DEPTH);
+            let collides = property(&layer.props, "collides") != Some("false");
+            // each gid the layer uses becomes one of its tiles
+            let mut gids: Vec<u32> = vec![];
+            let mut cells = Vec::with_capacity(layer.data.len());
+            for gid in &layer.data {
+                let idx = gids.iter().position(|g| g == gid).unwrap_or_else(|| {
+                    gids.push(*gid);
+                    gids.len() - 1
+                });
+                cells.push(u8::try_from(idx).map_err(|_| TiledError::TooManyTiles {
+                    layer: layer.name.clone(),
+                })?);
+            }
+            let tiles = gids
+                .iter()
+                .map(|gid| self.tile(*gid, depth))
+                .collect::<Result<_, _>>()?;
+            layers.push(Layer::new(
+                &layer.name,
+                depth,
+                layer.visible,
+                collides,
+                Grid::new(w, h, cells),
+                tiles,
+            ));
+        }
+        let mut starts = vec![];
+        for object in &self.objects {
+            let entity = E::from_str(&object.entity).map_err(|_| TiledError::UnknownEntity {
+                name: object.entity.clone(),
+            })?;
+            let (x, y) = object.center;
+            let (cx, cy) = (x / tile_sz as f32, y / tile_sz as f32);
+            if cx < 0.0 || cy < 0.0 || cx >= w as f32 || cy >= h as f32 {
+                return Err(TiledError::OutOfBounds {
+                    name: object.entity.clone(),
+                });
+            }
+            // the same spot a level file start in that cell would have
+            let (cx, cy) = (cx as usize, cy as usize);
+            starts.push((
+                entity,
+                Vec2 {
+                    x: (cx * tile_sz) as f32 + tile_sz as f32 / 2.0,
+                    y: ((h - cy) * tile_sz) as f32 - tile_sz as f32 / 2.0,
+                },
+            ));
+        }
+        let name = property(&self.props, "name").unwrap_or("map");
+        Ok(Level::new(name, tile_sz, layers, starts))
+    }
+}
+
+/// Where a `<property>` in a TMX file belongs
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    Map,
+    Tile(u32),
+    Layer,
+    Other,
+}
+
+fn attributes(e: &BytesStart) -> Result<HashMap<String, String>, TiledError> {
+    let mut attrs = HashMap::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        attrs.insert(
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            attr.unescape_value()?.into_owned(),
+        );
+    }
+    Ok(attrs)
+}
+
+fn attribute<T: FromStr>(
+    attrs: &HashMap<String, String>,
+    name: &'static str,
+) -> Result<Option<T>, TiledError> {
+    attrs.get(name).map(|text| number(name, text)).transpose()
+}
+
+fn required<T: FromStr>(
+    attrs: &HashMap<String, String>,
+    name: &'static str,
+) -> Result<T, TiledError> {
+    attribute(attrs, name)?.ok_or(TiledError::Missing { what: name })
+}
+
+fn parse_tmx(s: &str) -> Result<Map, TiledError> {
+    let mut reader = quick_xml::Reader::from_str(s);
+    let mut map = Map::default();
+    let mut owner = Owner::Map;
+    let mut in_data = false;
+    loop {
+        let (e, empty) = match reader.read_event()? {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::Text(text) if in_data => {
+                let layer = map
+                    .layers
+                    .last_mut()
+                    .ok_or(TiledError::Missing { what: "layer" })?;
+                layer.data.extend(parse_csv(&text.unescape()?)?);
+                continue;
+            }
+            Event::End(e) => {
+                match e.name().as_ref() {
+                    b"tile" if !in_data => owner = Owner::Other,
+                    b"layer" | b"objectgroup" | b"object" => owner = Owner::Other,
+                    b"data" => in_data = false,
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let attrs = attributes(&e)?;
+        match e.name().as_ref() {
+            b"map" => {
+                map.width = required(&attrs, "width")?;
+                map.height = required(&attrs, "height")?;
+                map.tile_w = required(&attrs, "tilewidth")?;
+                map.tile_h = required(&attrs, "tileheight")?;
+                if attrs.get("infinite").is_some_and(|i| i == "1") {
+                    return Err(TiledError::Unsupported {
+                        what: "infinite maps",
+                    });
+                }
+            }
+            b"tileset" => {
+                if attrs.contains_key("source") {
+                    return Err(TiledError::Unsupported {
+                        what: "external tilesets",
+                    });
+                }
+                let columns = required(&attrs, "columns")?;
+                if columns == 0 {
+                    return Err(TiledError::Unsupported {
+                        what: "image collection tilesets",
+                    });
+                }
+                map.tilesets.push(Tileset {
+                    first_gid: required(&attrs, "firstgid")?,
+                    columns,
+                    tile_w: required(&attrs, "tilewidth")?,
+                    tile_h: required(&attrs, "tileheight")?,
+                    margin: attribute(&attrs, "margin")?.unwrap_or(0),
+                    spacing: attribute(&attrs, "spacing")?.unwrap_or(0),
+                    solid: vec![],
+                });
+                owner = Owner::Other;
+            }
+            b"tile" if in_data => {
+                let layer = map
+                    .layers
+                    .last_mut()
+                    .ok_or(TiledError::Missing { what: "layer" })?;
+                layer.data.push(attribute(&attrs, "gid")?.unwrap_or(0));
+            }
+            b"tile" if !empty => owner = Owner::Tile(required(&attrs, "id")?),
+            b"layer" => {
+                map.layers.push(TileLayer {
+                    name: attrs.get("name").cloned().unwrap_or_default(),
+                    visible: attrs.get("visible").is_none_or(|v| v != "0"),
+                    props: vec![],
+                    data: vec![],
+                });
+                owner = Owner::Layer;
+            }
+            b"data" => {
+                match attrs.get("encoding").map(String::as_str) {
+                    None | Some("csv") => {}
+                    Some(_) => {
+                        return Err(TiledError::Unsupported {
+                            what: "base64 layers",
+                        })
+                    }
+                }
+                in_data = !empty;
+            }
+            b"object" => {
+                let entity = ["class", "type", "name"]
+                    .iter()
+                    .find_map(|key| attrs.get(*key).filter(|v| !v.is_empty()))
+                    .ok_or(TiledError::Missing {
+                        what: "object class",
+                    })?
+                    .clone();
+                let x: f32 = required(&attrs, "x")?;
+                let y: f32 = required(&attrs, "y")?;
+                let w: f32 = attribute(&attrs, "width")?.unwrap_or(0.0);
+                let h: f32 = attribute(&attrs, "height")?.unwrap_or(0.0);
+                // tile objects hang up from their bottom left corner
+                let top = if attrs.contains_key("gid") { y - h } else { y };
+                map.objects.push(Object {
+                    entity,
+                    center: (x + w / 2.0, top + h / 2.0),
+                });
+                owner = Owner::Other;
+            }
+            b"property" => {
+                let name = attrs.get("name").cloned().unwrap_or_default();
+                let value = attrs.get("value").cloned().unwrap_or_default();
+                match owner {
+                    Owner::Map => map.props.push((name, value)),
+                    Owner::Layer => map
+                        .layers
+                        .last_mut()
+                        .ok_or(TiledError::Missing { what: "layer" })?
+                        .props
+                        .push((name, value)),
+                    Owner::Tile(id) if name == "solid" && value == "true" => map
+                        .tilesets
+                        .last_mut()
+                        .ok_or(TiledError::Missing { what: "tileset" })?
+                        .solid
+                        .push(id),
+                    _ => {}
+                }
+            }
+            b"group" => {
+                return Err(TiledError::Unsupported {
+                    what: "group layers",
+                })
+            }
+            b"imagelayer" => owner = Owner::Other,
+            _ => {}
+        }
+    }
+    if map.width == 0 || map.height == 0 {
+        return Err(TiledError::Missing { what: "map size" });
+    }
+    Ok(map)
+}
+
+fn json_number<T: FromStr>(value: &Value, what: &'static str) -> Result<T, TiledError> {
+    match value.get(what) {
+        None => Err(TiledError::Missing { what }),
+        Some(v) => number(what, &v.to_string()),
+    }
+}
+
+fn json_str<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+}
+
+fn json_properties(value: &Value) -> Properties {
+    let props = value.get("properties").and_then(Value::as_array);
+    props
+        .into_iter()
+        .flatten()
+        .filter_map(|prop| {
+            let name = json_str(prop, "name")?.to_string();
+            let value = prop.get("value")?;
+            let value = value
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| value.to_string());
+            Some((name, value))
+        })
+        .collect()
+}
+
+fn json_array<'a>(value: &'a Value, what: &'static str) -> Result<&'a Vec<Value>, TiledError> {
+    value
+        .get(what)
+        .and_then(Value::as_array)
+        .ok_or(TiledError::Missing { what })
+}
+
+fn parse_tmj(s: &str) -> Result<Map, TiledError> {
+    let json: Value = serde_json::from_str(s)?;
+    if json.get("infinite").and_then(Value::as_bool) == Some(true) {
+        return Err(TiledError::Unsupported {
+            what: "infinite maps",
+        });
+    }
+    let mut map = Map {
+        width: json_number(&json, "width")?,
+        height: json_number(&json, "height")?,
+        tile_w: json_number(&json, "tilewidth")?,
+        tile_h: json_number(&json, "tileheight")?,
+        props: json_properties(&json),
+        ..Map::default()
+    };
+    for tileset in json_array(&json, "tilesets")? {
+        if tileset.get("source").is_some() {
+            return Err(TiledError::Unsupported {
+                what: "external tilesets",
+            });
+        }
+        let tiles = tileset.get("tiles").and_then(Value::as_array);
+        let solid = tiles
+            .into_iter()
+            .flatten()
+            .filter(|tile| property(&json_properties(tile), "solid") == Some("true"))
+            .map(|tile| json_number(tile, "id"))
+            .collect::<Result<_, _>>()?;
+        let columns = json_number(tileset, "columns")?;
+        if columns == 0 {
+            return Err(TiledError::Unsupported {
+                what: "image collection tilesets",
+            });
+        }
+        map.tilesets.push(Tileset {
+            first_gid: json_number(tileset, "firstgid")?,
+            columns,
+            tile_w: json_number(tileset, "tilewidth")?,
+            tile_h: json_number(tileset, "tileheight")?,
+            margin: json_number(tileset, "margin").unwrap_or(0),
+            spacing: json_number(tileset, "spacing").unwrap_or(0),
+            solid,
+        });
+    }
+    for layer in json_array(&json, "layers")? {
+        match json_str(layer, "type") {
+            Some("tilelayer") => {
+                if layer.get("encoding").and_then(Value::as_str) == Some("base64") {
+                    return Err(TiledError::Unsupported {
+                        what: "base64 layers",
+                    });
+                }
+                let data = json_array(layer, "data")?
+                    .iter()
+                    .map(|gid| number("tile gid", &gid.to_string()))
+                    .collect::<Result<_, _>>()?;
+                map.layers.push(TileLayer {
+                    name: json_str(layer, "name").unwrap_or_default().to_string(),
+                    visible: layer.get("visible").and_then(Value::as_bool) != Some(false),
+                    props: json_properties(layer),
+                    data,
+                });
+            }
+            Some("objectgroup") => {
+                for object in json_array(layer, "objects")? {
+                    let entity = ["class", "type", "name"]
+                        .iter()
+                        .find_map(|key| json_str(object, key))
+                        .ok_or(TiledError::Missing {
+                            what: "object class",
+                        })?
+                        .to_string();
+                    let x: f32 = json_number(object, "x")?;
+                    let y: f32 = json_number(object, "y")?;
+                    let w: f32 = json_number(object, "width").unwrap_or(0.0);
+                    let h: f32 = json_number(object, "height").unwrap_or(0.0);
+                    // tile objects hang up from their bottom left corner
+                    let top = if object.get("gid").is_some() {
+                        y - h
+                    } else {
+                        y
+                    };
+                    map.objects.push(Object {
+                        entity,
+                        center: (x + w / 2.0, top + h / 2.0),
+                    });
+                }
+            }
+            Some("group") => {
+                return Err(TiledError::Unsupported {
+                    what: "group layers",
+                })
+            }
+            _ => {}
+        }
+    }
+    Ok(map)
+}
+
+impl<E: FromStr> Level<E> {
+    /// Imports a Tiled map saved as TMX (XML)
+    pub fn from_tmx(s: &str) -> Result<Self, TiledError> {
+        parse_tmx(s)?.into_level()
+    }
+    /// Imports a Tiled map saved as TMJ (JSON)
+    pub fn from_tmj(s: &str) -> Result<Self, TiledError> {
+        parse_tmj(s)?.into_level()
+    }
+    /// Imports a Tiled map saved as either TMX or TMJ
+    pub fn from_tiled(s: &str) -> Result<Self, TiledError> {
+        if s.trim_start().starts_with('<') {
+            Self::from_tmx(s)
+        } else {
+            Self::from_tmj(s)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Entity {
+        Snake,
+        Apple,
+    }
+    impl FromStr for Entity {
+        type Err = ();
+        fn from_str(s: &str) -> Result<Self, ()> {
+            match s {
+                "snake" => Ok(Entity::Snake),
+                "apple" => Ok(Entity::Apple),
+                _ => Err(()),
+            }
+        }
+    }
+    type Level = super::Level<Entity>;
+
+    const ROOM_TXT: &str = include_str!("../fixtures/room.txt");
+    const ROOM_TMX: &str = include_str!("../fixtures/room_tiled.tmx");
+    const ROOM_TMJ: &str = include_str!("../fixtures/room_tiled.tmj");
+
+    /// Everything about a level's layers that shows up in play
+    fn describe(level: &Level) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} {}x{} tile size {}",
+            level.name(),
+            level.width(),
+            level.height(),
+            level.tile_size()
+        )];
+        for layer in level.layers() {
+            lines.push(format!(
+                "layer {} depth {} visible {} collides {}",
+                layer.name(),
+                layer.depth(),
+                layer.is_visible(),
+                layer.collides()
+            ));
+            for y in 0..level.height() {
+                for x in 0..level.width() {
+                    let tile = layer.get_tile((x, y)).unwrap();
+                    let r = tile.sheet_region;
+                    lines.push(format!(
+                        "{x},{y}: sheet {} {} {} {}x{} depth {} solid {}",
+                        r.sheet, r.x, r.y, r.w, r.h, r.depth, tile.solid
+                    ));
+                }
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn test_fixtures_match_level_file() {
+        let txt = Level::parse(ROOM_TXT).unwrap();
+        for level in [Level::from_tmx(ROOM_TMX), Level::from_tiled(ROOM_TMJ)] {
+            let level = level.unwrap();
+            assert_eq!(describe(&level), describe(&txt));
+            assert_eq!(level.starts(), txt.starts());
+            assert!(level.get_tile((0, 0)).unwrap().solid);
+            assert!(!level.get_tile((1, 1)).unwrap().solid);
+        }
+        assert_eq!(
+            txt.starts(),
+            [
+                (Entity::Snake, Vec2 { x: 12.0, y: 12.0 }),
+                (Entity::Apple, Vec2 { x: 20.0, y: 12.0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        assert!(matches!(
+            Level::from_tmx(&ROOM_TMX.replace("type=\"snake\"", "type=\"dragon\"")),
+            Err(TiledError::UnknownEntity { .. })
+        ));
+        assert!(matches!(
+            Level::from_tmx(&ROOM_TMX.replace("encoding=\"csv\"", "encoding=\"base64\"")),
+            Err(TiledError::Unsupported { .. })
+        ));
+        assert!(matches!(
+            Level::from_tmj(&ROOM_TMJ.replace("\"firstgid\": 1", "\"firstgid\": 3")),
+            Err(TiledError::NoTileset { gid: 1 })
+        ));
+        assert!(matches!(
+            Level::from_tmj(&ROOM_TMJ.replace("\"x\": 8", "\"x\": 80")),
+            Err(TiledError::OutOfBounds { .. })
+        ));
+        // tile data or tile properties with nothing to belong to
+        let header = "<map width=\"2\" height=\"1\" tilewidth=\"8\" tileheight=\"8\">";
+        assert!(matches!(
+            Level::from_tmx(&format!("{header}<data encoding=\"csv\">1,1</data></map>")),
+            Err(TiledError::Missing { what: "layer" })
+        ));
+        let solid = "<properties><property name=\"solid\" value=\"true\"/></properties>";
+        assert!(matches!(
+            Level::from_tmx(&format!("{header}<tile id=\"0\">{solid}</tile></map>")),
+            Err(TiledError::Missing { what: "tileset" })
+        ));
+        // tiles with no size
+        assert!(matches!(
+            Level::from_tmx(&ROOM_TMX.replace(
+                "tilewidth=\"8\" tileheight=\"8\" infinite",
+                "tilewidth=\"0\" tileheight=\"0\" infinite"
+            )),
+            Err(TiledError::BadValue {
+                what: "tile size",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Level::from_tmj(&ROOM_TMJ.replace("\n  \"tilewidth\": 8", "\n  \"tilewidth\": 0")),
+            Err(TiledError::BadValue {
+                what: "tile size",
+                ..
+            })
+        ));
+        // image collection tilesets are saved with no columns
+        assert!(matches!(
+            Level::from_tmx(&ROOM_TMX.replace("columns=\"2\"", "columns=\"0\"")),
+            Err(TiledError::Unsupported {
+                what: "image collection tilesets"
+            })
+        ));
+        assert!(matches!(
+            Level::from_tmj(&ROOM_TMJ.replace("\"columns\": 2", "\"columns\": 0")),
+            Err(TiledError::Unsupported {
+                what: "image collection tilesets"
+            })
+        ));
+        assert!(matches!(
+            Level::from_tmj("{\"width\": 4"),
+            Err(TiledError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_from_assets() {
+        let cache = crate::app::Content::Dir("fixtures").into_assets();
+        let tmx = Level::load(&cache, "room_tiled");
+        assert_eq!(describe(&tmx), describe(&Level::load(&cache, "room")));
+    }
+}
@@ -1,10 +1,8 @@
-use assets_manager::{asset::Png, AssetCache};
+use assets_manager::asset::Png;
 use frenderer::{
-    input::{Input, Key},
     sprites::{Camera2D, SheetRegion, Transform},
-    wgpu, Immediate, Renderer,
+    wgpu, Immediate,
 };
-use wgpu::naga::back::msl::EntryPointError;
 
 // Ayelet - I cannot run this line. It seems to step from differences in hardware that I guess my machine
 // doesnt' support. I changed it to the following two lines which seems to solve the issue for me.
@@ -13,36 +11,200 @@ use wgpu::naga::back::msl::EntryPointError;
 #[cfg(target_arch = "aarch64")]
 use std::arch::aarch64::float32x2_t;
 use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::str::FromStr;
 
 // use std::collections::VecDeque;
 
-use engine::{grid::Grid, *};
+use engine::actions::ActionMap;
+use engine::anim::{AnimPlayer, AnimSet};
+use engine::app::{AppConfig, Assets, GameRng, SpriteSink};
+use engine::campaign::{Campaign, Outcome, Progress};
+use engine::input::Input;
+use engine::save::{HighScores, SaveError, Snapshot, Storage};
+use engine::scene::{Scene, SceneStack, Transition};
+use engine::text::{Align, Font};
 use engine::{level::Level, *};
 
 const TILE_SZ: usize = 8;
 const W: usize = 240;
 const H: usize = 240;
-pub const PI: f32 = 3.14159265358979323846264338327950288_f32; // 3.1415926535897931f64
 const DT: f32 = 1.0 / 60.0;
 const CLAW_ROT_VEL: f32 = 0.1;
+const CLAW_OPEN: &str = "claw_open";
+const CLAW_CLOSED: &str = "claw_closed";
 const CHAIN_SIZE: f32 = 8.0;
+const CLAW_WEIGHT: f32 = 1.0;
+
+/// Entities that can start in a level file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Entity {
+    Gold,
+    Silver,
+    Rock,
+    Gem,
+    Claw,
+}
+
+impl FromStr for Entity {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "gold" => Ok(Entity::Gold),
+            "silver" => Ok(Entity::Silver),
+            "rock" => Ok(Entity::Rock),
+            "gem" => Ok(Entity::Gem),
+            "claw" => Ok(Entity::Claw),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Entity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Entity::Gold => "gold",
+            Entity::Silver => "silver",
+            Entity::Rock => "rock",
+            Entity::Gem => "gem",
+            Entity::Claw => "claw",
+        })
+    }
+}
+
+/// What the player can do, bound to keys in controls.txt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    /// Sends the claw down
+    Fire,
+    Confirm,
+    Pause,
+}
+
+impl FromStr for Action {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "fire" => Ok(Action::Fire),
+            "confirm" => Ok(Action::Confirm),
+            "pause" => Ok(Action::Pause),
+            _ => Err(()),
+        }
+    }
+}
 
 struct Game {
+    scenes: SceneStack<Screen>,
     claw: Claw,
     score: usize,
-    current_level: Level,
-    levels: Vec<Level>,
+    campaign: Campaign<Entity>,
     entities: Vec<Object>,
-    timer: usize,
+    /// Score when the current level started, to go back to on failure
+    level_start_score: usize,
+    progress: Progress,
+    actions: ActionMap<Action>,
+    anims: AnimSet,
+    storage: Storage,
+    /// The game in progress when we last quit, resumed instead of
+    /// starting a new one
+    saved: Option<Snapshot>,
+    high_scores: HighScores,
+    font: Font,
     frame_counter: usize,
     move_interval: usize,
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Screen {
+    Title,
+    Playing,
+    Paused,
+    GameOver { won: bool, score: usize },
+}
+
+impl Scene for Screen {
+    type Context = Game;
+    fn enter(&mut self, game: &mut Game) {
+        if *self == Screen::Playing {
+            match game.saved.take().map(|snapshot| game.restore(&snapshot)) {
+                Some(Ok(())) => {}
+                Some(Err(e)) => {
+                    eprintln!("Couldn't resume saved game: {e}");
+                    game.restart_level();
+                }
+                None => game.restart_level(),
+            }
+        }
+    }
+    fn simulate(&mut self, game: &mut Game, input: &Input, dt: f32) -> Transition<Self> {
+        let confirm = game.actions.any_pressed(input, Action::Confirm);
+        let pause = game.actions.any_pressed(input, Action::Pause);
+        match self {
+            Screen::Title if confirm => Transition::Replace(Screen::Playing),
+            Screen::Playing if pause => Transition::Push(Screen::Paused),
+            Screen::Playing => match game.play(input, dt) {
+                Outcome::Playing => Transition::Stay,
+                outcome => {
+                    game.record_score();
+                    Transition::Replace(Screen::GameOver {
+                        won: outcome == Outcome::Won,
+                        score: game.score,
+                    })
+                }
+            },
+            Screen::Paused if pause => Transition::Pop,
+            Screen::GameOver { won, .. } if confirm => {
+                // a lost level gets another try, a won campaign starts over
+                if *won {
+                    game.new_game();
+                }
+                Transition::Replace(Screen::Playing)
+            }
+            _ => Transition::Stay,
+        }
+    }
+    fn render(&self, game: &Game, frend: &mut impl SpriteSink) {
+        match self {
+            Screen::Title => {
+                game.draw_world(frend);
+                let start = if game.saved.is_some() {
+                    "CONTINUE"
+                } else {
+                    "START"
+                };
+                game.draw_message(frend, &format!("GOLD MINER\n\nSPACE TO {start}"));
+            }
+            Screen::Playing => {
+                game.draw_world(frend);
+                game.draw_hud(frend);
+            }
+            Screen::Paused => game.draw_message(frend, "PAUSED"),
+            Screen::GameOver { won, score } => {
+                game.draw_world(frend);
+                let result = if *won { "YOU WIN!" } else { "TIME UP" };
+                let retry = if *won { "PLAY AGAIN" } else { "RETRY" };
+                let best = game.high_scores.best(game.campaign.name()).unwrap_or(0);
+                game.draw_message(
+                    frend,
+                    &format!("{result}\n\nSCORE ${score}\nBEST ${best}\n\nSPACE TO {retry}"),
+                );
+            }
+        }
+    }
+    fn is_overlay(&self) -> bool {
+        *self == Screen::Paused
+    }
+}
+
 struct Claw {
     dir: f32,
     body: VecDeque<Vec2>,
     is_deployed: bool,
     velo_dir: bool,
     claw_dir: bool,
+    /// How far the claw has reeled toward its next chain link
+    reel: f32,
+    /// Open while empty, closed around whatever it's carrying
+    anim: AnimPlayer,
 }
 
 impl Claw {
@@ -56,15 +218,6 @@ impl Claw {
         }
     }
 
-    pub fn to_rect(&self) -> Rect {
-        Rect {
-            x: self.body[0].x - (TILE_SZ as f32 / 2.0),
-            y: self.body[0].y - (TILE_SZ as f32 / 2.0),
-            h: TILE_SZ as u16,
-            w: TILE_SZ as u16,
-        }
-    }
-
     pub fn chain_transform(&self, index: usize) -> Transform {
         Transform {
             x: self.body.get(index).unwrap().x,
@@ -78,13 +231,38 @@ impl Claw {
 
 struct Object {
     pos: Vec2,
-    e_type: EntityType,
+    e_type: Entity,
     is_moving: bool,
     value: usize,
+    /// How much this slows the claw down, relative to the empty claw
+    weight: f32,
     picked_up: bool,
+    /// Plays the animation named after `e_type`
+    anim: AnimPlayer,
 }
 
 impl Object {
+    /// A fresh object to pick up, or `None` for entities that aren't
+    /// objects
+    fn new(e_type: Entity, pos: Vec2) -> Option<Self> {
+        let (value, weight) = match e_type {
+            Entity::Claw => return None,
+            Entity::Rock => (22, 3.0),
+            Entity::Gem => (500, 0.5),
+            Entity::Gold => (250, 2.0),
+            Entity::Silver => (100, 1.0),
+        };
+        Some(Object {
+            pos,
+            e_type,
+            is_moving: false,
+            value,
+            weight,
+            picked_up: false,
+            anim: AnimPlayer::new(&e_type.to_string()),
+        })
+    }
+
     pub fn transform(&self) -> Transform {
         Transform {
             x: self.pos.x,
@@ -94,119 +272,86 @@ impl Object {
             rot: 0.0,
         }
     }
-
-    pub fn uv(&self) -> SheetRegion {
-        match self.e_type {
-            EntityType::Gold => GOLD[0],
-            EntityType::Silver => SILVER[0],
-            EntityType::Rock => ROCK[0],
-            EntityType::Gem => GEM[0],
-            _ => panic!("can't draw this type"),
-        }
-        .with_depth(1)
-    }
-
-    pub fn to_rect(&self) -> Rect {
-        Rect {
-            x: self.pos.x - (TILE_SZ as f32 / 2.0),
-            y: self.pos.y - (TILE_SZ as f32 / 2.0),
-            h: TILE_SZ as u16,
-            w: TILE_SZ as u16,
-        }
-    }
 }
 
-#[derive(Debug, PartialEq)]
-struct Contact {
-    rect_a: Rect,
-    index_a: usize,
-    rect_b: Rect,
-    index_b: usize,
-    overlap: Vec2,
-}
+// font.png: 3x5 glyphs for ' ' through 'Z' in 4x6 cells
+const FONT_REGION: SheetRegion = SheetRegion::rect(0, 0, 64, 24);
+const TEXT_GROUP: usize = 1;
+const TEXT_SCALE: f32 = 2.0;
 
-// 8 by 8 coordinates, related to Goldminer_tilesheet1
-const CLAW: [SheetRegion; 1] = [SheetRegion::rect(1, 56, 8, 16)];
-const GOLD: [SheetRegion; 1] = [SheetRegion::rect(1, 10, 8, 8)];
-const SILVER: [SheetRegion; 1] = [SheetRegion::rect(1, 19, 8, 8)];
-const ROCK: [SheetRegion; 1] = [SheetRegion::rect(1, 27, 8, 8)];
-const GEM: [SheetRegion; 1] = [SheetRegion::rect(1, 47, 8, 8)];
+// 8 by 8 coordinates, related to Goldminer_tilesheet1.  The claw and
+// objects are animated, see anims.txt.
 const CHAIN: [SheetRegion; 1] = [SheetRegion::rect(1, 74, 8, 8)];
 
 fn main() {
-    #[cfg(not(target_arch = "wasm32"))]
-    let source =
-        assets_manager::source::FileSystem::new("content").expect("Couldn't load resources");
-    #[cfg(target_arch = "wasm32")]
-    let source = assets_manager::source::Embedded::from(assets_manager::source::embed!("content"));
-    let cache = assets_manager::AssetCache::with_source(source);
-
-    let drv = frenderer::Driver::new(
-        winit::window::WindowBuilder::new()
-            .with_title("test")
-            .with_inner_size(winit::dpi::LogicalSize::new(1024.0, 768.0)),
-        Some((W as u32, H as u32)),
+    engine::app::run::<Game>(
+        AppConfig {
+            title: "Goldminer",
+            render_size: (W as u32, H as u32),
+            dt: DT,
+            content: engine::content!(),
+            ..AppConfig::default()
+        }
+        .with_args(),
     );
+}
 
-    let mut input = Input::default();
-
-    let mut now = frenderer::clock::Instant::now();
-    let mut acc = 0.0;
-    drv.run_event_loop::<(), _>(
-        move |window, frend| {
-            let mut frend = Immediate::new(frend);
-            let game = Game::new(&mut frend, cache);
-            (window, game, frend)
-        },
-        move |event, target, (window, ref mut game, ref mut frend)| {
-            use winit::event::{Event, WindowEvent};
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    target.exit();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::Resized(size),
-                    ..
-                } => {
-                    if !frend.gpu().is_web() {
-                        frend.resize_surface(size.width, size.height);
-                    }
-                    window.request_redraw();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::RedrawRequested,
-                    ..
-                } => {
-                    let elapsed = now.elapsed().as_secs_f32();
-                    // You can add the time snapping/death spiral prevention stuff here if you want.
-                    // I'm not using it here to keep the starter code small.
-                    acc += elapsed;
-                    now = std::time::Instant::now();
-                    // While we have time to spend
-                    while acc >= DT {
-                        // simulate a frame
-                        acc -= DT;
-                        game.simulate(&input, DT);
-                        input.next_frame();
-                    }
-                    game.render(frend);
-                    frend.render();
-                    window.request_redraw();
-                }
-                event => {
-                    input.process_input_event(&event);
-                }
+impl engine::app::Game for Game {
+    fn new(cache: &Assets, _rng: GameRng, mut storage: Storage) -> Self {
+        let campaign = Campaign::load(cache, "campaign");
+        let mut claw_body: VecDeque<Vec2> = VecDeque::new();
+        claw_body.push_back(Vec2 {
+            x: TILE_SZ as f32 * 15.0,
+            y: TILE_SZ as f32 * 25.0,
+        });
+        let entities = spawn_objects(campaign.level());
+        let anims = AnimSet::load(cache, "anims");
+        if let Some(name) = missing_anim(&anims) {
+            panic!("anims.txt has no {name:?} animation");
+        }
+        let mut game = Game {
+            scenes: SceneStack::default(),
+            claw: Claw {
+                dir: 0.0,
+                body: claw_body,
+                is_deployed: false,
+                velo_dir: false,
+                claw_dir: true,
+                reel: 0.0,
+                anim: AnimPlayer::new(CLAW_OPEN),
+            },
+            score: 0,
+            campaign,
+            entities,
+            level_start_score: 0,
+            progress: Progress::default(),
+            actions: ActionMap::load(cache, "controls"),
+            anims,
+            saved: storage.load_snapshot(SNAPSHOT_NAME, SNAPSHOT_VERSION),
+            high_scores: storage.load_high_scores(),
+            storage,
+            font: Font::fixed_width(FONT_REGION, ' '..='Z', 4, 6),
+            frame_counter: 0,
+            move_interval: 5,
+        };
+        game.scenes = SceneStack::new(Screen::Title, &mut game);
+        game
+    }
+
+    /// Saves the game in progress to pick up next time.  Otherwise the
+    /// last save is forgotten, unless it's still waiting to be continued.
+    fn quit(&mut self) {
+        let playing = matches!(self.scenes.top(), Some(Screen::Playing | Screen::Paused));
+        if playing {
+            if let Err(e) = self.storage.save_snapshot(&self.snapshot()) {
+                eprintln!("Couldn't save game: {e}");
             }
-        },
-    )
-    .expect("event loop error");
-}
+        } else if self.saved.is_none() {
+            self.storage.clear_snapshot();
+        }
+    }
 
-impl Game {
-    fn new(renderer: &mut Immediate, cache: AssetCache) -> Self {
+    fn init_graphics(&mut self, renderer: &mut Immediate, cache: &Assets) {
         let tile_handle = cache
             .load::<Png>("Goldminer_tilesheet1")
             .expect("Couldn't load tilesheet img");
@@ -217,19 +362,11 @@ impl Game {
             tile_img.dimensions(),
             Some("tiles-sprites"),
         );
-        // Ayelet: Changed this to be only one level
-        let level = engine::level::Level::from_str(
-            &cache
-                .load::<String>("level")
-                .expect("Couldn't access level.txt")
-                .read(),
-            TILE_SZ,
-        );
-        // let current_level = 0; // For future if we want to add more levels?
         let camera = Camera2D {
             screen_pos: [0.0, 0.0],
             screen_size: [W as f32, H as f32],
         };
+        let level = self.campaign.level();
         let sprite_estimate = level.sprite_count() + level.starts().len();
         renderer.sprite_group_add(
             &tile_tex,
@@ -237,102 +374,124 @@ impl Game {
             vec![SheetRegion::ZERO; sprite_estimate],
             camera,
         );
-        let mut claw_body: VecDeque<Vec2> = VecDeque::new();
-        claw_body.push_back(Vec2 {
-            x: TILE_SZ as f32 * 15.0,
-            y: TILE_SZ as f32 * 25.0,
-        });
-        let mut entities: Vec<Object> = vec![];
-        for (etype, pos) in level.starts().iter() {
-            match etype {
-                EntityType::Claw => {}
-                EntityType::Rock => entities.push(Object {
-                    pos: *pos,
-                    e_type: EntityType::Rock,
-                    is_moving: false,
-                    value: 22,
-                    picked_up: false,
-                }),
-                EntityType::Gem => entities.push(Object {
-                    pos: *pos,
-                    e_type: EntityType::Gem,
-                    is_moving: false,
-                    value: 500,
-                    picked_up: false,
-                }),
-                EntityType::Gold => entities.push(Object {
-                    pos: *pos,
-                    e_type: EntityType::Gold,
-                    is_moving: false,
-                    value: 250,
-                    picked_up: false,
-                }),
-                EntityType::Silver => entities.push(Object {
-                    pos: *pos,
-                    e_type: EntityType::Silver,
-                    is_moving: false,
-                    value: 100,
-                    picked_up: false,
-                }),
-                EntityType::Snake => {}
-                EntityType::Food => {} // EntityType::Door(_rm, _x, _y) => {}
-                                       // EntityType::Enemy => self.enemies.push(Pos {
-                                       //     pos: *pos,
-                                       //     dir: Dir::S,
-                                       // }),
-            }
-        }
-        let mut game = Game {
-            claw: Claw {
-                dir: 0.0,
-                body: claw_body,
-                is_deployed: false,
-                velo_dir: false,
-                claw_dir: true,
-            },
-            score: 0,
-            current_level: level,
-            levels: vec![],
-            entities: entities,
-            timer: 30,
-            frame_counter: 0,
-            move_interval: 5,
-        };
-        game
+        let font_handle = cache.load::<Png>("font").expect("Couldn't load font img");
+        let font_img = font_handle.read().0.to_rgba8();
+        let font_tex = renderer.create_array_texture(
+            &[&font_img],
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            font_img.dimensions(),
+            Some("font-sprites"),
+        );
+        renderer.sprite_group_add(&font_tex, vec![], vec![], camera);
     }
 
-    fn render(&mut self, frend: &mut Immediate) {
-        self.current_level.render_immediate(frend);
-        frend.draw_sprite(0, self.claw.transform(), CLAW[0]);
+    fn render(&mut self, frend: &mut impl SpriteSink) {
+        let scenes = std::mem::take(&mut self.scenes);
+        scenes.render(self, frend);
+        self.scenes = scenes;
+    }
 
-        for (i, chain) in self.claw.body.iter().enumerate() {
-            if i == 0 {
-                continue;
-            }
+    fn simulate(&mut self, input: &Input, dt: f32) {
+        let mut scenes = std::mem::take(&mut self.scenes);
+        scenes.simulate(self, input, dt);
+        self.scenes = scenes;
+    }
+}
+
+/// The first animation the claw or an object plays that `anims` is
+/// missing, if any
+fn missing_anim(anims: &AnimSet) -> Option<String> {
+    let objects = [Entity::Gold, Entity::Silver, Entity::Rock, Entity::Gem].map(|e| e.to_string());
+    objects
+        .into_iter()
+        .chain([CLAW_OPEN, CLAW_CLOSED].map(str::to_string))
+        .find(|name| !anims.contains(name))
+}
+
+/// The collectable objects that start in `level`
+fn spawn_objects(level: &Level<Entity>) -> Vec<Object> {
+    level
+        .starts()
+        .iter()
+        .filter_map(|(etype, pos)| Object::new(*etype, *pos))
+        .collect()
+}
+
+impl Game {
+    fn draw_world(&self, frend: &mut impl SpriteSink) {
+        self.campaign.level().render_immediate(frend);
+        frend.draw_sprite(0, self.claw.transform(), self.claw.anim.frame(&self.anims));
+
+        for i in 1..self.claw.body.len() {
             frend.draw_sprite(0, self.claw.chain_transform(i), CHAIN[0]);
         }
         for obj in self.entities.iter() {
-            match obj.e_type {
-                EntityType::Gold => frend.draw_sprite(0, obj.transform(), GOLD[0]),
-                EntityType::Silver => frend.draw_sprite(0, obj.transform(), SILVER[0]),
-                EntityType::Rock => frend.draw_sprite(0, obj.transform(), ROCK[0]),
-                EntityType::Gem => frend.draw_sprite(0, obj.transform(), GEM[0]),
-                EntityType::Snake => continue,
-                EntityType::Food => continue,
-                EntityType::Claw => continue,
-            }
+            frend.draw_sprite(0, obj.transform(), obj.anim.frame(&self.anims));
         }
     }
 
-    fn simulate(&mut self, input: &Input, dt: f32) {
+    /// The score and goal top left, and the time left top right
+    fn draw_hud(&self, frend: &mut impl SpriteSink) {
+        let top = H as f32 - 2.0;
+        let score = match self.campaign.goals().target_score {
+            Some(goal) => format!("${}/{goal}", self.score),
+            None => format!("${}", self.score),
+        };
+        self.font.draw(
+            frend,
+            TEXT_GROUP,
+            &score,
+            Vec2::new(2.0, top),
+            Align::Left,
+            TEXT_SCALE,
+        );
+        if let Some(left) = self.time_left() {
+            self.font.draw_number(
+                frend,
+                TEXT_GROUP,
+                left.ceil() as i64,
+                2,
+                Vec2::new(W as f32 - 2.0, top),
+                Align::Right,
+                TEXT_SCALE,
+            );
+        }
+    }
+
+    /// Centered lines of text in the middle of the screen
+    fn draw_message(&self, frend: &mut impl SpriteSink, text: &str) {
+        let height = self.font.measure(text, TEXT_SCALE).y;
+        self.font.draw(
+            frend,
+            TEXT_GROUP,
+            text,
+            Vec2::new(W as f32 / 2.0, (H as f32 + height) / 2.0),
+            Align::Center,
+            TEXT_SCALE,
+        );
+    }
+
+    /// Runs the claw for one tick.  Returns [`Outcome::Won`] once the
+    /// last level of the campaign is beaten, and [`Outcome::Lost`] when
+    /// time runs out on a level.
+    fn play(&mut self, input: &Input, dt: f32) -> Outcome {
+        self.progress.elapsed += dt;
+        let holding = self.entities.iter().any(|e| e.picked_up);
+        self.claw
+            .anim
+            .play(if holding { CLAW_CLOSED } else { CLAW_OPEN });
+        self.claw.anim.update(dt);
+        for entity in self.entities.iter_mut() {
+            entity.anim.update(dt);
+        }
         self.frame_counter += 1;
         if self.frame_counter >= self.move_interval {
-            if input.is_key_down(Key::Space) && self.claw.is_deployed != true {
+            if self.actions.player(0).is_down(input, Action::Fire) && !self.claw.is_deployed {
                 self.claw.is_deployed = true;
             }
             // rotate claw
-            if self.claw.is_deployed == false {
-                if self.claw.velo_dir == true {
+            if !self.claw.is_deployed {
+                if self.claw.velo_dir {
                     if self.claw.dir > 1.0 {
                         self.claw.velo_dir = !self.claw.velo_dir;
                     } else {
@@ -348,17 +507,16 @@ impl Game {
             }
 
             // move claw
-            if self.claw.is_deployed == true {
+            if self.claw.is_deployed {
                 // shoot claw
-                if self.claw.claw_dir == true {
-                    let curr_x = self.claw.body.front().unwrap().x;
-                    let curr_y = self.claw.body.front().unwrap().y;
-                    let new_x: f32 = curr_x + CHAIN_SIZE * f32::cos(self.claw.dir - PI / 2.0);
-                    let new_y: f32 = curr_y + CHAIN_SIZE * f32::sin(self.claw.dir - PI / 2.0);
-                    self.claw.body.push_front(Vec2 { x: new_x, y: new_y });
+                if self.claw.claw_dir {
+                    let curr = *self.claw.body.front().unwrap();
+                    self.claw
+                        .body
+                        .push_front(curr + Vec2::from_angle(self.claw.dir - PI / 2.0) * CHAIN_SIZE);
 
                     for entity in self.entities.iter_mut() {
-                        if self.claw.body.front().unwrap().distance(&entity.pos) < CHAIN_SIZE
+                        if self.claw.body.front().unwrap().distance(entity.pos) < CHAIN_SIZE
                             && !entity.picked_up
                         {
                             entity.picked_up = true;
@@ -369,23 +527,36 @@ impl Game {
                 // retract claw
                 {
                     if self.claw.body.len() > 1 {
-                        self.claw.body.pop_front();
+                        // heavier loads reel in more slowly
+                        self.claw.reel += CLAW_WEIGHT / self.load_weight();
+                        if self.claw.reel >= 1.0 {
+                            self.claw.reel -= 1.0;
+                            self.claw.body.pop_front();
+                        }
                         for entity in self.entities.iter_mut().filter(|e| e.picked_up) {
                             entity.pos = *self.claw.body.front().unwrap();
                         }
                     } else {
                         self.claw.is_deployed = false;
                         self.claw.claw_dir = true;
+                        self.claw.reel = 0.0;
+                        // cash in whatever came up with the claw
+                        self.score += self
+                            .entities
+                            .iter()
+                            .filter(|e| e.picked_up)
+                            .map(|e| e.value)
+                            .sum::<usize>();
                         self.entities.retain(|e| !e.picked_up);
                     }
                 }
             }
 
             // change claw direction when claw gets outside map
-            if self.claw.body.get(0).unwrap().x < 0.0
-                || self.claw.body.get(0).unwrap().y < 0.0
-                || self.claw.body.get(0).unwrap().x >= W as f32
-                || self.claw.body.get(0).unwrap().y >= H as f32
+            if self.claw.body.front().unwrap().x < 0.0
+                || self.claw.body.front().unwrap().y < 0.0
+                || self.claw.body.front().unwrap().x >= W as f32
+                || self.claw.body.front().unwrap().y >= H as f32
             {
                 self.claw.claw_dir = !self.claw.claw_dir;
             }
@@ -398,68 +569,365 @@ impl Game {
                 }
             }
 
-            for entity in self.entities.iter_mut() {
-                if entity.is_moving == true {}
-            }
-
             self.frame_counter = 0;
         }
-        let mut object_contacts: Vec<Contact> = Vec::new();
-        let mut object_to_remove: Vec<usize> = Vec::new();
-        let object_rects: Vec<Rect> = self.entities.iter().map(|pos| pos.to_rect()).collect();
-        let claw_rect: Rect = self.claw.to_rect();
-
-        gather_contact(&[claw_rect], &object_rects, &mut object_contacts);
-        // println!("{:?}", object_contacts);
-
-        // sort objects contacts vector
-        object_contacts.sort_by(|a, b| {
-            b.overlap.mag_sq().partial_cmp(&a.overlap.mag_sq()).unwrap()
-            // _or(std::cmp::Ordering::Equal)
-        });
+        self.progress.score = self.score;
+        match self.campaign.goals().outcome(&self.progress) {
+            Outcome::Won if !self.campaign.is_last() => {
+                self.next_level();
+                Outcome::Playing
+            }
+            outcome => outcome,
+        }
+    }
 
-        for c in object_contacts.drain(..) {
-            if !object_to_remove.contains(&c.index_b) {
-                object_to_remove.push(c.index_b);
+    /// The claw's weight plus everything it's holding
+    fn load_weight(&self) -> f32 {
+        CLAW_WEIGHT
+            + self
+                .entities
+                .iter()
+                .filter(|e| e.picked_up)
+                .map(|e| e.weight)
+                .sum::<f32>()
+    }
+
+    /// Seconds left on the current level's clock, if it has one
+    fn time_left(&self) -> Option<f32> {
+        self.campaign.goals().time_left(&self.progress)
+    }
+
+    /// Moves on to the next level in the campaign, keeping the score.
+    fn next_level(&mut self) {
+        self.campaign.advance();
+        self.level_start_score = self.score;
+        self.restart_level();
+    }
+
+    /// Goes back to the first level with no score
+    fn new_game(&mut self) {
+        self.campaign.restart();
+        self.level_start_score = 0;
+        self.restart_level();
+    }
+
+    /// Starts the current level over with the score it started with
+    fn restart_level(&mut self) {
+        self.score = self.level_start_score;
+        self.progress = Progress::default();
+        self.entities = spawn_objects(self.campaign.level());
+        self.claw.body.truncate(1);
+        self.claw.is_deployed = false;
+        self.claw.claw_dir = true;
+        self.claw.reel = 0.0;
+    }
+
+    /// Adds the score from a finished level to its high scores
+    fn record_score(&mut self) {
+        let level = self.campaign.name().to_string();
+        if self.high_scores.insert(&level, self.score).is_some() {
+            if let Err(e) = self.storage.save_high_scores(&self.high_scores) {
+                eprintln!("Couldn't save high scores: {e}");
             }
         }
-        // object_to_remove.sort();
-        // for index in object_to_remove.iter().rev() {
-        //     self.entities.swap_remove(*index);
-        // }
-
-        fn gather_contact(a_rects: &[Rect], b_rects: &[Rect], contacts_list: &mut Vec<Contact>) {
-            for (i, a_rect) in a_rects.iter().enumerate() {
-                for (j, b_rect) in b_rects.iter().enumerate() {
-                    if let Some(overlap) = a_rect.overlap(*b_rect) {
-                        contacts_list.push(Contact {
-                            index_a: i,
-                            rect_a: *a_rect,
-                            index_b: j,
-                            rect_b: *b_rect,
-                            overlap: overlap,
-                        })
-                    }
-                }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new(SNAPSHOT_NAME, SNAPSHOT_VERSION);
+        snapshot.set("level", self.campaign.name());
+        snapshot.set("score", self.score);
+        snapshot.set("level_start_score", self.level_start_score);
+        snapshot.set("elapsed", self.progress.elapsed);
+        let claw = &self.claw;
+        snapshot.set("claw_dir", claw.dir);
+        snapshot.set_pairs("claw_body", claw.body.iter().map(|p| (p.x, p.y)));
+        snapshot.set("claw_deployed", claw.is_deployed);
+        snapshot.set("claw_swinging_right", claw.velo_dir);
+        snapshot.set("claw_going_down", claw.claw_dir);
+        snapshot.set("claw_reel", claw.reel);
+        let objects = &self.entities;
+        snapshot.set_list("objects", objects.iter().map(|o| o.e_type));
+        snapshot.set_pairs("object_pos", objects.iter().map(|o| (o.pos.x, o.pos.y)));
+        snapshot.set_list("object_moving", objects.iter().map(|o| o.is_moving));
+        snapshot.set_list("object_picked_up", objects.iter().map(|o| o.picked_up));
+        snapshot
+    }
+
+    /// Picks up the game saved in `snapshot`.  Leaves the game as it was
+    /// if the snapshot doesn't fit this campaign.
+    fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SaveError> {
+        let bad = |key: &str| SaveError::BadValue {
+            key: key.to_string(),
+            text: String::new(),
+        };
+        let level: String = snapshot.get("level")?;
+        let claw_body: VecDeque<Vec2> = snapshot
+            .get_pairs("claw_body")?
+            .into_iter()
+            .map(|(x, y)| Vec2 { x, y })
+            .collect();
+        if claw_body.is_empty() {
+            return Err(bad("claw_body"));
+        }
+        let types: Vec<Entity> = snapshot.get_list("objects")?;
+        let pos = snapshot.get_pairs("object_pos")?;
+        let moving: Vec<bool> = snapshot.get_list("object_moving")?;
+        let picked_up: Vec<bool> = snapshot.get_list("object_picked_up")?;
+        if [pos.len(), moving.len(), picked_up.len()] != [types.len(); 3] {
+            return Err(bad("objects"));
+        }
+        let mut entities = vec![];
+        for (i, e_type) in types.into_iter().enumerate() {
+            let (x, y) = pos[i];
+            let mut object = Object::new(e_type, Vec2 { x, y }).ok_or_else(|| bad("objects"))?;
+            object.is_moving = moving[i];
+            object.picked_up = picked_up[i];
+            entities.push(object);
+        }
+        let claw = Claw {
+            dir: snapshot.get("claw_dir")?,
+            body: claw_body,
+            is_deployed: snapshot.get("claw_deployed")?,
+            velo_dir: snapshot.get("claw_swinging_right")?,
+            claw_dir: snapshot.get("claw_going_down")?,
+            reel: snapshot.get("claw_reel")?,
+            anim: AnimPlayer::new(CLAW_OPEN),
+        };
+        let score = snapshot.get("score")?;
+        let level_start_score = snapshot.get("level_start_score")?;
+        let elapsed = snapshot.get("elapsed")?;
+        if !self.campaign.jump_to(&level) {
+            return Err(bad("level"));
+        }
+        self.claw = claw;
+        self.entities = entities;
+        self.score = score;
+        self.level_start_score = level_start_score;
+        self.progress = Progress {
+            elapsed,
+            ..Progress::default()
+        };
+        Ok(())
+    }
+}
+
+const SNAPSHOT_NAME: &str = "goldminer";
+/// Bump whenever what's in a snapshot changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::headless::{Headless, InputScript};
+    use engine::input::Key;
+
+    /// A game past its title screen
+    fn playing() -> Headless<Game> {
+        let mut h = Headless::<Game>::new(engine::content!());
+        h.input_mut().press(Key::Enter);
+        h.step();
+        h.input_mut().release(Key::Enter);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        h
+    }
+
+    #[test]
+    fn test_goal_advances_level() {
+        let mut h = playing();
+        let goal = h.game().campaign.goals().target_score.unwrap();
+        h.game_mut().score = goal - 1;
+        h.step();
+        assert_eq!(h.game().campaign.index(), 0);
+        h.game_mut().score = goal;
+        h.step();
+        let game = h.game();
+        assert_eq!(game.campaign.index(), 1);
+        assert_eq!(game.score, goal);
+        assert_eq!(
+            game.entities.len(),
+            spawn_objects(game.campaign.level()).len()
+        );
+    }
+    #[test]
+    fn test_hud() {
+        let mut h = playing();
+        h.game_mut().score = 120;
+        let goal = h.game().campaign.goals().target_score.unwrap();
+        let limit = h.game().campaign.goals().time_limit.unwrap() as usize;
+        // score and goal on the left, seconds left on the right
+        let hud = format!("$120/{goal}").len() + limit.to_string().len();
+        assert_eq!(h.render().sprite_count(TEXT_GROUP), hud);
+    }
+    /// Deploys the claw as if it had just grabbed `entities[idx]`
+    /// `links` chain links away.
+    fn grab(game: &mut Game, idx: usize, links: usize) {
+        let top = game.claw.body[0];
+        for _ in 0..links {
+            game.claw.body.push_front(top);
+        }
+        game.claw.is_deployed = true;
+        game.claw.claw_dir = false;
+        game.entities[idx].picked_up = true;
+    }
+    /// Steps until the claw is back up, failing if it takes too long
+    fn reel_in(h: &mut Headless<Game>) {
+        for _ in 0..600 {
+            if !h.game().claw.is_deployed {
+                break;
             }
+            h.step();
         }
+        assert!(!h.game().claw.is_deployed, "claw never came back up");
+    }
+    fn ticks_to_reel_in(e_type: Entity) -> usize {
+        let mut h = playing();
+        let idx = h
+            .game()
+            .entities
+            .iter()
+            .position(|e| e.e_type == e_type)
+            .unwrap();
+        let value = h.game().entities[idx].value;
+        grab(h.game_mut(), idx, 4);
+        let count = h.game().entities.len();
+        reel_in(&mut h);
+        let game = h.game();
+        assert_eq!(game.score, value);
+        assert_eq!(game.entities.len(), count - 1);
+        h.tick()
+    }
 
-        // fn compute_displacement(a: Rect, b: Rect) -> Vec2 {
-        //     let Some(mut overlap) = a.overlap(b) else {
-        //         return Vec2 { x: 0.0, y: 0.0 };
-        //     };
-        //     if overlap.y < overlap.x {
-        //         overlap.x = 0.0;
-        //     } else {
-        //         overlap.y = 0.0;
-        //     }
-        //     if a.x < b.x {
-        //         overlap.x *= -1.0;
-        //     }
-        //     if a.y < b.y {
-        //         overlap.y *= -1.0;
-        //     }
-        //     return overlap;
-        // }
+    #[test]
+    fn test_objects_score_and_weigh() {
+        let gem = ticks_to_reel_in(Entity::Gem);
+        let silver = ticks_to_reel_in(Entity::Silver);
+        let rock = ticks_to_reel_in(Entity::Rock);
+        assert!(gem < silver && silver < rock, "{gem} {silver} {rock}");
+    }
+    #[test]
+    fn test_required_anims() {
+        assert_eq!(missing_anim(&playing().game().anims), None);
+        let claw_only = "anim claw_open once\nframe 1 56 8 16 1\n\
+                         anim claw_closed once\nframe 10 56 8 16 1\n";
+        let anims = AnimSet::parse(claw_only).unwrap();
+        assert_eq!(missing_anim(&anims), Some("gold".to_string()));
+    }
+    #[test]
+    fn test_claw_closes_on_catch() {
+        let mut h = playing();
+        assert_eq!(h.game().claw.anim.name(), CLAW_OPEN);
+        grab(h.game_mut(), 0, 4);
+        h.step();
+        assert_eq!(h.game().claw.anim.name(), CLAW_CLOSED);
+        reel_in(&mut h);
+        h.step();
+        assert_eq!(h.game().claw.anim.name(), CLAW_OPEN);
+    }
+    #[test]
+    fn test_timer_runs_out() {
+        let mut h = playing();
+        let limit = h.game().campaign.goals().time_limit.unwrap();
+        h.game_mut().level_start_score = 10;
+        h.game_mut().score = 20;
+        h.game_mut().entities.pop();
+        let ticks = (limit * 60.0) as usize;
+        h.run(&InputScript::new(), ticks - 1);
+        assert_eq!(h.game().score, 20);
+        h.run(&InputScript::new(), 2);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::GameOver {
+                won: false,
+                score: 20
+            })
+        );
+        // retrying puts the level and the score back how they were
+        h.input_mut().press(Key::Space);
+        h.step();
+        let game = h.game();
+        assert_eq!(game.campaign.index(), 0);
+        assert_eq!(game.score, 10);
+        assert!(game.progress.elapsed < 1.0);
+        assert_eq!(
+            game.entities.len(),
+            spawn_objects(game.campaign.level()).len()
+        );
+    }
+    #[test]
+    fn test_save_and_resume() {
+        let mut h = playing();
+        h.game_mut().score = 40;
+        h.game_mut().entities.pop();
+        // quit with the claw on its way down
+        let t = h.tick();
+        let script = InputScript::new()
+            .press(t, Key::Space)
+            .release(t + 6, Key::Space);
+        h.run(&script, 30);
+        assert!(h.game().claw.is_deployed);
+        h.quit();
+        let game = h.game();
+        let claw_body = game.claw.body.clone();
+        let objects: Vec<(Entity, Vec2)> =
+            game.entities.iter().map(|o| (o.e_type, o.pos)).collect();
+        let elapsed = game.progress.elapsed;
+
+        // closing the window on the title screen keeps the save
+        let mut h = Headless::<Game>::with_storage(engine::content!(), game.storage.clone());
+        assert!(h.game().saved.is_some());
+        h.quit();
+        let mut h = Headless::<Game>::with_storage(engine::content!(), h.game().storage.clone());
+        assert!(h.game().saved.is_some());
+        h.run(&InputScript::new().tap(h.tick(), Key::Enter), 1);
+        let game = h.game();
+        assert_eq!(game.scenes.top(), Some(&Screen::Playing));
+        assert_eq!(game.score, 40);
+        assert_eq!(game.progress.elapsed, elapsed);
+        assert_eq!(game.claw.body, claw_body);
+        assert!(game.claw.is_deployed);
+        let resumed: Vec<(Entity, Vec2)> =
+            game.entities.iter().map(|o| (o.e_type, o.pos)).collect();
+        assert_eq!(resumed, objects);
+
+        // running out of time keeps the score as the level's best
+        let limit = h.game().campaign.goals().time_limit.unwrap();
+        h.game_mut().progress.elapsed = limit;
+        h.step();
+        assert!(matches!(
+            h.game().scenes.top(),
+            Some(Screen::GameOver { .. })
+        ));
+        h.quit();
+        let h = Headless::<Game>::with_storage(engine::content!(), h.game().storage.clone());
+        let game = h.game();
+        assert!(game.saved.is_none());
+        assert_eq!(game.high_scores.best(game.campaign.name()), Some(40));
+    }
+    #[test]
+    fn test_campaign_won() {
+        let mut h = playing();
+        let last = h.game().campaign.len() - 1;
+        for _ in 0..last {
+            h.game_mut().next_level();
+        }
+        let goal = h.game().campaign.goals().target_score.unwrap();
+        h.game_mut().score = goal;
+        h.step();
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::GameOver {
+                won: true,
+                score: goal
+            })
+        );
+        // the clock stops on the game-over screen
+        let elapsed = h.game().progress.elapsed;
+        h.run(&InputScript::new(), 10);
+        assert_eq!(h.game().progress.elapsed, elapsed);
+        h.input_mut().press(Key::Space);
+        h.step();
+        let game = h.game();
+        assert_eq!(game.scenes.top(), Some(&Screen::Playing));
+        assert_eq!((game.campaign.index(), game.score), (0, 0));
     }
 }
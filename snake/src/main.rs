@@ -1,153 +1,328 @@
-use assets_manager::{asset::Png, AssetCache};
+use assets_manager::asset::Png;
 use frenderer::{
-    input::{Input, Key},
     sprites::{Camera2D, SheetRegion, Transform},
-    wgpu, Immediate, Renderer,
+    wgpu, Immediate,
 };
 use rand::Rng;
 use std::collections::VecDeque;
 use std::str::FromStr;
 
-use engine::{grid::Grid, *};
+use engine::actions::{ActionMap, Bindings, TurnBuffer};
+use engine::ai;
+use engine::app::{AppConfig, Assets, GameRng, SpriteSink};
+use engine::autotile::{self, Piece};
+use engine::campaign::{Campaign, Outcome, Progress};
+use engine::grid::{Coord, Grid};
+use engine::input::Input;
+use engine::save::{HighScores, SaveError, Snapshot, Storage};
+use engine::scene::{Scene, SceneStack, Transition};
+use engine::text::{Align, Font};
 use engine::{level::Level, *};
 
+/// Entities that can start in a level file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Entity {
+    Snake,
+    Apple,
+    /// Where the computer's snake starts, if rivals are on
+    Rival,
+}
+
+impl FromStr for Entity {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "snake" => Ok(Entity::Snake),
+            "apple" => Ok(Entity::Apple),
+            "rival" => Ok(Entity::Rival),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What the player can do, bound to keys in controls.txt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Confirm,
+    Pause,
+    /// Turns the rival snake on or off from the title screen
+    Rival,
+}
+
+impl FromStr for Action {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "left" => Ok(Action::Left),
+            "right" => Ok(Action::Right),
+            "up" => Ok(Action::Up),
+            "down" => Ok(Action::Down),
+            "confirm" => Ok(Action::Confirm),
+            "pause" => Ok(Action::Pause),
+            "rival" => Ok(Action::Rival),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which way each turning action steers, in the order they're queued
+const TURNS: [(Action, Dir); 4] = [
+    (Action::Left, Dir::Left),
+    (Action::Right, Dir::Right),
+    (Action::Up, Dir::Up),
+    (Action::Down, Dir::Down),
+];
+
 struct Game {
-    started: bool,
+    scenes: SceneStack<Screen>,
     snake: Snake,
+    /// The computer's snake, racing the player to the apples
+    rival: Option<Snake>,
+    /// Whether levels with a rival start get a rival
+    rivals: bool,
     apple: Apple,
-    level: Level,
+    campaign: Campaign<Entity>,
+    progress: Progress,
+    /// Apples eaten since the last game over
+    score: usize,
+    actions: ActionMap<Action>,
+    /// Turns pressed since the snake last moved
+    turns: TurnBuffer,
+    /// Where apples go; seeded by the engine so runs can be replayed
+    rng: GameRng,
+    storage: Storage,
+    /// The game in progress when we last quit, resumed instead of
+    /// starting a new one
+    saved: Option<Snapshot>,
+    high_scores: HighScores,
+    font: Font,
     frame_counter: usize,
     move_interval: usize,
 }
 
-struct Snake {
-    dir: Dir,
-    body: VecDeque<Vec2>,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Screen {
+    Title,
+    Playing,
+    Paused,
+    GameOver { score: usize, level: usize },
 }
 
-impl Snake {
-    pub fn transform(&self, index: usize) -> Transform {
-        Transform {
-            x: self.body.get(index).unwrap().x,
-            y: self.body.get(index).unwrap().y,
-            w: 4,
-            h: 4,
-            rot: 0.0,
+impl Scene for Screen {
+    type Context = Game;
+    fn enter(&mut self, game: &mut Game) {
+        if *self == Screen::Playing {
+            match game.saved.take().map(|snapshot| game.restore(&snapshot)) {
+                Some(Ok(())) => {}
+                Some(Err(e)) => {
+                    eprintln!("Couldn't resume saved game: {e}");
+                    game.new_game();
+                }
+                None => game.new_game(),
+            }
         }
     }
+    fn simulate(&mut self, game: &mut Game, input: &Input, dt: f32) -> Transition<Self> {
+        let controls = game.controls();
+        let confirm = controls.is_pressed(input, Action::Confirm);
+        let pause = controls.is_pressed(input, Action::Pause);
+        match self {
+            Screen::Title | Screen::GameOver { .. } if confirm => {
+                Transition::Replace(Screen::Playing)
+            }
+            Screen::Title if controls.is_pressed(input, Action::Rival) => {
+                game.rivals = !game.rivals;
+                Transition::Stay
+            }
+            Screen::Playing if pause => Transition::Push(Screen::Paused),
+            Screen::Playing if !game.play(input, dt) => {
+                game.record_score();
+                Transition::Replace(Screen::GameOver {
+                    score: game.score,
+                    level: game.campaign.index() + 1,
+                })
+            }
+            Screen::Paused if pause => Transition::Pop,
+            _ => Transition::Stay,
+        }
+    }
+    fn render(&self, game: &Game, frend: &mut impl SpriteSink) {
+        match self {
+            Screen::Title => {
+                game.draw_world(frend);
+                let rivals = if game.rivals { "ON" } else { "OFF" };
+                let start = if game.saved.is_some() {
+                    "CONTINUE"
+                } else {
+                    "START"
+                };
+                game.draw_message(
+                    frend,
+                    &format!("SNAKE\n\nR: RIVAL {rivals}\n\nSPACE TO {start}"),
+                );
+            }
+            Screen::Playing => {
+                game.draw_world(frend);
+                game.draw_hud(frend);
+            }
+            Screen::Paused => game.draw_message(frend, "PAUSED"),
+            Screen::GameOver { score, level } => {
+                game.draw_world(frend);
+                let best = game.high_scores.best(game.campaign.name()).unwrap_or(0);
+                game.draw_message(
+                    frend,
+                    &format!(
+                        "GAME OVER\n\nLEVEL {level}\nSCORE {score}\nBEST {best}\n\nSPACE TO RETRY"
+                    ),
+                );
+            }
+        }
+    }
+    fn is_overlay(&self) -> bool {
+        *self == Screen::Paused
+    }
 }
 
-struct Apple {
-    pos: Vec2,
+/// The snake's body in grid cells, head first
+struct Snake {
+    dir: Dir,
+    body: VecDeque<Coord>,
+    /// Cells left to grow from apples it's eaten
+    growing: usize,
 }
 
-impl Apple {
-    pub fn transform(&self) -> Transform {
-        Transform {
-            x: self.pos.x,
-            y: self.pos.y,
-            w: 4,
-            h: 4,
-            rot: 0.0,
+impl Snake {
+    /// A snake of `len` cells with its head at `head`, trailing off to
+    /// the left (or as far as the map allows).
+    fn new(head: Coord, len: usize) -> Self {
+        let mut body = VecDeque::from([head]);
+        while body.len() < len {
+            let Some(next) = Dir::Left.step(*body.back().unwrap()) else {
+                break;
+            };
+            body.push_back(next);
+        }
+        Snake {
+            dir: Dir::Right,
+            body,
+            growing: 0,
         }
     }
+    /// Moves the tail up a cell, unless the snake is still growing
+    fn move_tail(&mut self) {
+        if self.growing > 0 {
+            self.growing -= 1;
+        } else {
+            self.body.pop_back();
+        }
+    }
+}
+
+struct Apple {
+    pos: Coord,
 }
 
-const TILE_SZ: usize = 4;
+const SNAKE_LEN: usize = 5;
+/// How many cells a snake grows for each apple
+const APPLE_GROWTH: usize = 3;
 //change as needed
 const W: usize = 120;
 const H: usize = 120;
 const DT: f32 = 1.0 / 60.0;
 
-struct Contact {
-    rect_a: Rect,
-    index_a: usize,
-    rect_b: Rect,
-    index_b: usize,
-    overlap: Vec2,
-}
+// font.png: 3x5 glyphs for ' ' through 'Z' in 4x6 cells
+const FONT_REGION: SheetRegion = SheetRegion::rect(0, 0, 64, 24);
+const TEXT_GROUP: usize = 1;
 
 const FOOD: [SheetRegion; 1] = [SheetRegion::rect(533, 39, 4, 4)];
 
-const SNAKE: [SheetRegion; 2] = [
-    SheetRegion::rect(190, 345, 4, 4),
-    SheetRegion::rect(190, 395, 4, 4),
-];
+/// Sprites for each kind of segment, drawn joining on their left side
+/// and turned to fit, see [`autotile::Tile`]
+struct Skin {
+    /// Facing right
+    head: SheetRegion,
+    tail: SheetRegion,
+    straight: SheetRegion,
+    /// Joining on the left and bottom
+    corner: SheetRegion,
+}
+
+const SNAKE_SKIN: Skin = Skin {
+    head: SheetRegion::rect(976, 0, 4, 4),
+    tail: SheetRegion::rect(981, 0, 4, 4),
+    straight: SheetRegion::rect(986, 0, 4, 4),
+    corner: SheetRegion::rect(991, 0, 4, 4),
+};
+/// The rival's body is yellow
+const RIVAL_SKIN: Skin = Skin {
+    head: SNAKE_SKIN.head,
+    tail: SheetRegion::rect(981, 5, 4, 4),
+    straight: SheetRegion::rect(986, 5, 4, 4),
+    corner: SheetRegion::rect(991, 5, 4, 4),
+};
 
 fn main() {
-    #[cfg(not(target_arch = "wasm32"))]
-    let source =
-        assets_manager::source::FileSystem::new("content").expect("Couldn't load resources");
-    #[cfg(target_arch = "wasm32")]
-    let source = assets_manager::source::Embedded::from(assets_manager::source::embed!("content"));
-    let cache = assets_manager::AssetCache::with_source(source);
-
-    let drv = frenderer::Driver::new(
-        winit::window::WindowBuilder::new()
-            .with_title("test")
-            .with_inner_size(winit::dpi::LogicalSize::new(1024.0, 768.0)),
-        Some((W as u32, H as u32)),
+    engine::app::run::<Game>(
+        AppConfig {
+            title: "Snake",
+            render_size: (W as u32, H as u32),
+            dt: DT,
+            content: engine::content!(),
+            ..AppConfig::default()
+        }
+        .with_args(),
     );
+}
 
-    let mut input = Input::default();
-
-    let mut now = frenderer::clock::Instant::now();
-    let mut acc = 0.0;
-    drv.run_event_loop::<(), _>(
-        move |window, frend| {
-            let mut frend = Immediate::new(frend);
-            let game = Game::new(&mut frend, cache);
-            (window, game, frend)
-        },
-        move |event, target, (window, ref mut game, ref mut frend)| {
-            use winit::event::{Event, WindowEvent};
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    target.exit();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::Resized(size),
-                    ..
-                } => {
-                    if !frend.gpu().is_web() {
-                        frend.resize_surface(size.width, size.height);
-                    }
-                    window.request_redraw();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::RedrawRequested,
-                    ..
-                } => {
-                    let elapsed = now.elapsed().as_secs_f32();
-                    // You can add the time snapping/death spiral prevention stuff here if you want.
-                    // I'm not using it here to keep the starter code small.
-                    acc += elapsed;
-                    now = std::time::Instant::now();
-                    // While we have time to spend
-                    while acc >= DT {
-                        // simulate a frame
-                        acc -= DT;
-                        game.simulate(&input, DT);
-                        input.next_frame();
-                    }
-                    game.render(frend);
-                    frend.render();
-                    window.request_redraw();
-                }
-                event => {
-                    input.process_input_event(&event);
-                }
+impl engine::app::Game for Game {
+    fn new(cache: &Assets, rng: GameRng, mut storage: Storage) -> Self {
+        let campaign = Campaign::load(cache, "campaign");
+        let level = campaign.level();
+        let snake = Snake::new(start_cell(level, Entity::Snake), SNAKE_LEN);
+        let apple = Apple {
+            pos: start_cell(level, Entity::Apple),
+        };
+        let mut game = Game {
+            scenes: SceneStack::default(),
+            snake,
+            rival: None,
+            rivals: false,
+            apple,
+            campaign,
+            progress: Progress::default(),
+            score: 0,
+            actions: ActionMap::load(cache, "controls"),
+            turns: TurnBuffer::default(),
+            rng,
+            saved: storage.load_snapshot(SNAPSHOT_NAME, SNAPSHOT_VERSION),
+            high_scores: storage.load_high_scores(),
+            storage,
+            font: Font::fixed_width(FONT_REGION, ' '..='Z', 4, 6),
+            frame_counter: 0,
+            move_interval: 5,
+        };
+        game.scenes = SceneStack::new(Screen::Title, &mut game);
+        game
+    }
+
+    /// Saves the game in progress to pick up next time.  Otherwise the
+    /// last save is forgotten, unless it's still waiting to be continued.
+    fn quit(&mut self) {
+        let playing = matches!(self.scenes.top(), Some(Screen::Playing | Screen::Paused));
+        if playing {
+            if let Err(e) = self.storage.save_snapshot(&self.snapshot()) {
+                eprintln!("Couldn't save game: {e}");
             }
-        },
-    )
-    .expect("event loop error");
-}
+        } else if self.saved.is_none() {
+            self.storage.clear_snapshot();
+        }
+    }
 
-impl Game {
-    fn new(renderer: &mut Immediate, cache: AssetCache) -> Self {
+    fn init_graphics(&mut self, renderer: &mut Immediate, cache: &Assets) {
         let tile_handle = cache
             .load::<Png>("tilesheet")
             .expect("Couldn't load tilesheet img");
@@ -158,18 +333,11 @@ impl Game {
             tile_img.dimensions(),
             Some("tiles-sprites"),
         );
-        // Ayelet: Changed this to be only one level
-        let level = engine::level::Level::from_str(
-            &cache
-                .load::<String>("level")
-                .expect("Couldn't access level.txt")
-                .read(),
-        );
-        // let current_level = 0; // For future if we want to add more levels?
         let camera = Camera2D {
             screen_pos: [0.0, 0.0],
             screen_size: [W as f32, H as f32],
         };
+        let level = self.campaign.level();
         let sprite_estimate = level.sprite_count() + level.starts().len();
         renderer.sprite_group_add(
             &tile_tex,
@@ -177,212 +345,682 @@ impl Game {
             vec![SheetRegion::ZERO; sprite_estimate],
             camera,
         );
-        let mut snake_body: VecDeque<Vec2> = VecDeque::new();
-        for i in 0i8..5 {
-            let i = f32::from(i);
-            snake_body.push_back(Vec2 {
-                x: 100.0 + i * 4.0,
-                y: 100.0,
-            })
+        let font_handle = cache.load::<Png>("font").expect("Couldn't load font img");
+        let font_img = font_handle.read().0.to_rgba8();
+        let font_tex = renderer.create_array_texture(
+            &[&font_img],
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            font_img.dimensions(),
+            Some("font-sprites"),
+        );
+        renderer.sprite_group_add(&font_tex, vec![], vec![], camera);
+    }
+
+    fn render(&mut self, frend: &mut impl SpriteSink) {
+        let scenes = std::mem::take(&mut self.scenes);
+        scenes.render(self, frend);
+        self.scenes = scenes;
+    }
+
+    fn simulate(&mut self, input: &Input, dt: f32) {
+        let mut scenes = std::mem::take(&mut self.scenes);
+        scenes.simulate(self, input, dt);
+        self.scenes = scenes;
+    }
+}
+
+/// The cell of the first `entity` start in `level`
+fn start_cell(level: &Level<Entity>, entity: Entity) -> Coord {
+    let (_, pos) = level
+        .starts()
+        .iter()
+        .find(|(e, _)| *e == entity)
+        .unwrap_or_else(|| panic!("level {} has no {entity:?} start", level.name()));
+    level.world_to_grid(*pos)
+}
+
+/// The cell the rival starts in, if `level` has one
+fn rival_start(level: &Level<Entity>) -> Option<Coord> {
+    level
+        .starts()
+        .iter()
+        .find(|(e, _)| *e == Entity::Rival)
+        .map(|(_, pos)| level.world_to_grid(*pos))
+}
+
+impl Game {
+    /// The player's bindings
+    fn controls(&self) -> &Bindings<Action> {
+        self.actions.player(0)
+    }
+
+    fn draw_world(&self, frend: &mut impl SpriteSink) {
+        self.campaign.level().render_immediate(frend);
+        frend.draw_sprite(0, self.cell_transform(self.apple.pos), FOOD[0]);
+        self.draw_snake(frend, &self.snake, &SNAKE_SKIN);
+        if let Some(rival) = &self.rival {
+            self.draw_snake(frend, rival, &RIVAL_SKIN);
         }
-        let mut game = Game {
-            started: true,
-            snake: Snake {
-                dir: (Dir::Right),
-                body: (snake_body),
-            },
-            apple: Apple {
-                pos: Vec2 { x: 50.0, y: 50.0 },
-            },
-            level: level,
-            frame_counter: 0,
-            move_interval: 5,
-        };
-        game
     }
 
-    fn render(&mut self, frend: &mut Immediate) {
-        self.level.render_immediate(frend);
-        frend.draw_sprite(0, self.apple.transform(), FOOD[0]);
-        let mut count: usize = 0;
-        frend.draw_sprite(0, self.snake.transform(count), SNAKE[0]);
-        for vector2 in self.snake.body.iter() {
-            if (count == 0) {
-                count = count + 1;
-                continue;
+    /// Draws each segment as the head, tail, a straight or a corner,
+    /// turned to join the segments on either side
+    fn draw_snake(&self, frend: &mut impl SpriteSink, snake: &Snake, skin: &Skin) {
+        let masks = autotile::trail_masks(snake.body.iter().copied());
+        for (i, (cell, mask)) in snake.body.iter().zip(masks).enumerate() {
+            let (region, tile) = if i == 0 {
+                // the head faces the way it's going, even with no body
+                let neck = autotile::side_bit(snake.dir.opposite());
+                (skin.head, autotile::tile_4(neck))
+            } else {
+                let tile = autotile::tile_4(mask);
+                let region = match tile.piece {
+                    Piece::End => skin.tail,
+                    Piece::Corner => skin.corner,
+                    _ => skin.straight,
+                };
+                (region, tile)
             };
-            frend.draw_sprite(0, self.snake.transform(count), SNAKE[1]);
-            count = count + 1;
+            let mut trf = self.cell_transform(*cell);
+            trf.rot = tile.rot();
+            frend.draw_sprite(0, trf, region);
         }
     }
 
-    fn simulate(&mut self, input: &Input, dt: f32) {
+    /// The level number and apple count along the top
+    fn draw_hud(&self, frend: &mut impl SpriteSink) {
+        let top = H as f32 - 1.0;
+        let level = format!("LV{}", self.campaign.index() + 1);
+        self.font.draw(
+            frend,
+            TEXT_GROUP,
+            &level,
+            Vec2::new(1.0, top),
+            Align::Left,
+            1.0,
+        );
+        let apples = match self.campaign.goals().apples {
+            Some(goal) => format!("{}/{goal}", self.progress.apples),
+            None => self.progress.apples.to_string(),
+        };
+        self.font.draw(
+            frend,
+            TEXT_GROUP,
+            &apples,
+            Vec2::new(W as f32 - 1.0, top),
+            Align::Right,
+            1.0,
+        );
+    }
+
+    /// Centered lines of text in the middle of the screen
+    fn draw_message(&self, frend: &mut impl SpriteSink, text: &str) {
+        let height = self.font.measure(text, 1.0).y;
+        self.font.draw(
+            frend,
+            TEXT_GROUP,
+            text,
+            Vec2::new(W as f32 / 2.0, (H as f32 + height) / 2.0),
+            Align::Center,
+            1.0,
+        );
+    }
+
+    /// Advances the snake for one tick.  Returns false when the snake
+    /// dies or the level is lost.
+    fn play(&mut self, input: &Input, dt: f32) -> bool {
+        self.progress.elapsed += dt;
+        match self.campaign.goals().outcome(&self.progress) {
+            Outcome::Won => {
+                self.level_up();
+                return true;
+            }
+            Outcome::Lost => return false,
+            Outcome::Playing => {}
+        }
+        self.turns
+            .record(self.snake.dir, self.actions.player(0), input, &TURNS);
         self.frame_counter += 1;
         if self.frame_counter >= self.move_interval {
-            if input.is_key_down(Key::ArrowLeft) && self.snake.dir != Dir::Right {
-                self.snake.dir = Dir::Left;
-            } else if input.is_key_down(Key::ArrowRight) && self.snake.dir != Dir::Left {
-                self.snake.dir = Dir::Right;
-            } else if input.is_key_down(Key::ArrowUp) && self.snake.dir != Dir::Down {
-                self.snake.dir = Dir::Up;
-            } else if input.is_key_down(Key::ArrowDown) && self.snake.dir != Dir::Up {
-                self.snake.dir = Dir::Down;
-            }
-            let head_pos = self
-                .snake
-                .body
-                .front()
-                .expect("Snake body is empty")
-                .clone();
-            let new_head_pos = head_pos + self.snake.dir.to_vec2();
-            // coliision with the wall - restart game
-            if new_head_pos.x < 0.0
-                || new_head_pos.y < 0.0
-                || new_head_pos.x >= W as f32
-                || new_head_pos.y >= H as f32
-            {
-                self.restart();
-                return;
+            self.snake.dir = self.turns.next_turn(self.snake.dir);
+            let head = *self.snake.body.front().expect("Snake body is empty");
+            // collision with a wall or the edge of the map - game over
+            let Some(new_head) = self.snake.dir.step(head).filter(|cell| {
+                self.campaign
+                    .level()
+                    .get_tile(*cell)
+                    .is_some_and(|t| !t.solid)
+            }) else {
+                return false;
+            };
+            let eating = new_head == self.apple.pos;
+            if eating {
+                self.snake.growing += APPLE_GROWTH;
             }
-
-            if self.snake.body.contains(&new_head_pos) {
-                self.restart();
-                return;
+            // the tail moves out of the way this step
+            self.snake.move_tail();
+            let on_rival = self
+                .rival
+                .as_ref()
+                .is_some_and(|r| r.body.contains(&new_head));
+            if self.snake.body.contains(&new_head) || on_rival {
+                return false;
             }
-            if new_head_pos == self.apple.pos {
-                // 3 times to growth is a more noticlable
-                self.snake.body.push_front(new_head_pos);
-                self.snake.body.push_front(new_head_pos);
-                self.snake.body.push_front(new_head_pos);
+            self.snake.body.push_front(new_head);
+            if eating {
+                self.progress.apples += 1;
+                self.score += 1;
                 self.relocate_apple();
-            } else {
-                self.snake.body.push_front(new_head_pos);
-                self.snake.body.pop_back();
             }
+            self.move_rival();
             self.frame_counter = 0;
         }
+        true
+    }
+
+    /// Steers the rival along the shortest path to the apple, or towards
+    /// the most room when there's no way through.  A rival with nowhere
+    /// to go crashes and starts over.
+    fn move_rival(&mut self) {
+        let Some(rival) = &self.rival else {
+            return;
+        };
+        let blocked = self.blocked_cells();
+        let head = rival.body[0];
+        let dir = ai::astar_path(&blocked, head, self.apple.pos, |b| !*b)
+            .and_then(|path| ai::dir_between(head, *path.get(1)?))
+            .or_else(|| ai::roomiest_dir(&blocked, head, rival.dir, |b| !*b));
+        let next = dir.and_then(|d| d.step(head));
+        let (Some(dir), Some(next)) = (dir, next) else {
+            self.rival = rival_start(self.campaign.level()).map(|c| Snake::new(c, SNAKE_LEN));
+            return;
+        };
+        let rival = self.rival.as_mut().unwrap();
+        rival.dir = dir;
+        rival.body.push_front(next);
+        let eating = next == self.apple.pos;
+        if eating {
+            rival.growing += APPLE_GROWTH;
+        }
+        rival.move_tail();
+        if eating {
+            self.relocate_apple();
+        }
+    }
+
+    /// Cells a snake can't move into: walls and both snakes
+    fn blocked_cells(&self) -> Grid<bool> {
+        let level = self.campaign.level();
+        let (w, h) = (level.width(), level.height());
+        let mut blocked = Grid::new(
+            w,
+            h,
+            (0..w * h).map(|i| level.get_tile((i % w, i / w)).is_none_or(|t| t.solid)),
+        );
+        let rival = self.rival.iter().flat_map(|r| &r.body);
+        for &(x, y) in self.snake.body.iter().chain(rival) {
+            *blocked.get_mut(x, y).unwrap() = true;
+        }
+        blocked
+    }
+
+    /// A sprite transform covering grid cell `cell`
+    fn cell_transform(&self, cell: Coord) -> Transform {
+        let tile_sz = self.campaign.level().tile_size() as f32;
+        let corner = self.campaign.level().grid_to_world(cell);
+        (corner + Vec2::new(tile_sz / 2.0, tile_sz / 2.0)).to_transform(
+            tile_sz as u16,
+            tile_sz as u16,
+            0.0,
+        )
+    }
+
+    /// Open cells neither snake is on
+    fn free_cells(&self) -> Vec<Coord> {
+        let blocked = self.blocked_cells();
+        (0..blocked.width() * blocked.height())
+            .filter(|&i| !blocked[i])
+            .filter_map(|i| blocked.index_to_coord(i))
+            .collect()
     }
 
     fn relocate_apple(&mut self) {
-        // Ensure that the new apple position is not on the snake's body
-        loop {
-            let new_x = rand::thread_rng().gen_range(10..W - 10) as f32;
-            let new_y = rand::thread_rng().gen_range(10..H - 10) as f32;
-            let new_pos = Vec2 { x: new_x, y: new_y };
-            if !self.snake.body.contains(&new_pos) {
-                self.apple.pos = new_pos;
-                break;
-            }
+        let cells = self.free_cells();
+        if cells.is_empty() {
+            // the snake fills the whole map, nowhere left to put an apple
+            self.restart();
+            return;
         }
+        self.apple.pos = cells[self.rng.gen_range(0..cells.len())];
     }
 
+    /// Moves on to the next level in the campaign, or back to the first
+    /// once they've all been beaten.
+    fn level_up(&mut self) {
+        if !self.campaign.advance() {
+            self.campaign.restart();
+        }
+        self.restart();
+    }
+
+    /// Starts a fresh game from the first level
+    fn new_game(&mut self) {
+        self.score = 0;
+        self.campaign.restart();
+        self.restart();
+    }
+
+    /// Starts the current level over
     fn restart(&mut self) {
         self.frame_counter = 0;
+        self.turns.clear();
+        self.progress = Progress::default();
+        let level = self.campaign.level();
+        self.snake = Snake::new(start_cell(level, Entity::Snake), SNAKE_LEN);
+        self.rival = rival_start(level)
+            .filter(|_| self.rivals)
+            .map(|cell| Snake::new(cell, SNAKE_LEN));
+        self.apple.pos = start_cell(level, Entity::Apple);
+    }
 
-        // Reset snake position and direction
-        let initial_snake_length = 5; // for example, start with a length of 5
-        let mut initial_body: VecDeque<Vec2> = VecDeque::new();
-        let start_x = W as f32 / 2.0; // Start in the middle of the width
-        let start_y = H as f32 / 2.0; // Start in the middle of the height
-        for i in 0..initial_snake_length {
-            initial_body.push_back(Vec2 {
-                x: start_x - i as f32 * TILE_SZ as f32,
-                y: start_y,
-            });
+    /// Adds the score from a finished game to the current level's high
+    /// scores
+    fn record_score(&mut self) {
+        let level = self.campaign.name().to_string();
+        if self.high_scores.insert(&level, self.score).is_some() {
+            if let Err(e) = self.storage.save_high_scores(&self.high_scores) {
+                eprintln!("Couldn't save high scores: {e}");
+            }
         }
-        self.snake = Snake {
-            dir: Dir::Right, // Starting direction
-            body: initial_body,
-        };
+    }
 
-        // Reposition the apple
-        self.relocate_apple();
-    }
-
-    // fn simulate(&mut self, input: &Input, dt: f32) {
-    //     let mut dx = input.key_axis(Key::ArrowLeft, Key::ArrowRight) * DT;
-    //     let mut dy = input.key_axis(Key::ArrowDown, Key::ArrowUp) * DT;
-    // }
-
-    // let dest = self.player.pos + Vec2 { x: dx, y: dy };
-    // self.player.pos = dest;
-
-    // function to gather tile-entity contacts
-    // pub fn gather_tile_contacts(rects: &[Rect], level: &Level, contacts: &mut Vec<Contact>) {
-    //     for (i, rect) in rects.iter().enumerate() {
-    //         for (tr, _) in level.tiles_within(*rect).filter(|(_tr, td)| td.solid) {
-    //             if let Some(overlap) = rect.overlap(tr) {
-    //                 contacts.push(Contact {
-    //                     index_a: i,
-    //                     rect_a: *rect,
-    //                     index_b: 0,
-    //                     rect_b: tr,
-    //                     overlap: overlap,
-    //                 })
-    //             }
-    //         }
-    //     }
-    // }
-    // function to gather entity-entity contacts
-    fn gather_contact(a_rects: &[Rect], b_rects: &[Rect], contacts_list: &mut Vec<Contact>) {
-        for (i, a_rect) in a_rects.iter().enumerate() {
-            for (j, b_rect) in b_rects.iter().enumerate() {
-                if let Some(overlap) = a_rect.overlap(*b_rect) {
-                    contacts_list.push(Contact {
-                        index_a: i,
-                        rect_a: *a_rect,
-                        index_b: j,
-                        rect_b: *b_rect,
-                        overlap: overlap,
-                    })
-                }
+    fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new(SNAPSHOT_NAME, SNAPSHOT_VERSION);
+        snapshot.set("level", self.campaign.name());
+        snapshot.set("score", self.score);
+        snapshot.set("apples", self.progress.apples);
+        snapshot.set("elapsed", self.progress.elapsed);
+        snapshot.set("dir", self.snake.dir);
+        snapshot.set_pairs("body", self.snake.body.iter().copied());
+        snapshot.set("growing", self.snake.growing);
+        snapshot.set_pairs("apple", [self.apple.pos]);
+        snapshot.set("rivals", self.rivals);
+        if let Some(rival) = &self.rival {
+            snapshot.set("rival_dir", rival.dir);
+            snapshot.set_pairs("rival_body", rival.body.iter().copied());
+            snapshot.set("rival_growing", rival.growing);
+        }
+        snapshot
+    }
+
+    /// Picks up the game saved in `snapshot`.  Leaves the game as it was
+    /// if the snapshot doesn't fit this campaign.
+    fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SaveError> {
+        let level: String = snapshot.get("level")?;
+        let body: VecDeque<Coord> = snapshot.get_pairs("body")?.into();
+        let apple = snapshot.get_pairs("apple")?;
+        let bad = |key: &str, text: String| SaveError::BadValue {
+            key: key.to_string(),
+            text,
+        };
+        if body.is_empty() {
+            return Err(bad("body", String::new()));
+        }
+        let [apple] = apple[..] else {
+            return Err(bad("apple", format!("{apple:?}")));
+        };
+        let rival = if snapshot.has("rival_body") {
+            let body: VecDeque<Coord> = snapshot.get_pairs("rival_body")?.into();
+            if body.is_empty() {
+                return Err(bad("rival_body", String::new()));
             }
+            Some(Snake {
+                dir: snapshot.get("rival_dir")?,
+                body,
+                growing: snapshot.get("rival_growing")?,
+            })
+        } else {
+            None
+        };
+        let dir = snapshot.get("dir")?;
+        let growing = snapshot.get("growing")?;
+        let progress = Progress {
+            apples: snapshot.get("apples")?,
+            elapsed: snapshot.get("elapsed")?,
+            ..Progress::default()
+        };
+        let score = snapshot.get("score")?;
+        let rivals = snapshot.get("rivals")?;
+        let was = self.campaign.name().to_string();
+        if !self.campaign.jump_to(&level) {
+            return Err(bad("level", level));
+        }
+        // everything has to be on that level's map
+        let map = self.campaign.level();
+        let (w, h) = (map.width(), map.height());
+        let off_map = body
+            .iter()
+            .map(|c| ("body", *c))
+            .chain([("apple", apple)])
+            .chain(
+                rival
+                    .iter()
+                    .flat_map(|r| r.body.iter().map(|c| ("rival_body", *c))),
+            )
+            .find(|(_, (x, y))| *x >= w || *y >= h);
+        if let Some((key, cell)) = off_map {
+            self.campaign.jump_to(&was);
+            return Err(bad(key, format!("{cell:?}")));
         }
+        self.restart();
+        self.snake = Snake { dir, body, growing };
+        self.rival = rival;
+        self.rivals = rivals;
+        self.apple.pos = apple;
+        self.progress = progress;
+        self.score = score;
+        Ok(())
     }
+}
+
+const SNAPSHOT_NAME: &str = "snake";
+/// Bump whenever what's in a snapshot changes
+const SNAPSHOT_VERSION: u32 = 2;
 
-    //create empty vecs for different contacts
-    // let mut pl_contacts: Vec<Contact> = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::headless::{Headless, InputScript};
+    use engine::input::Key;
 
-    // // Get player's Rectengle
-    // let player_rect: Rect = self.player.to_rect();
+    /// A game that's past the title screen
+    fn playing() -> Headless<Game> {
+        let mut h = Headless::<Game>::new(engine::content!());
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Title));
+        h.input_mut().press(Key::Space);
+        h.step();
+        h.input_mut().release(Key::Space);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        h
+    }
 
-    // // gather player-tile contacts
-    // gather_tile_contacts(&[player_rect], &self.level(), &mut pl_contacts);
+    #[test]
+    fn test_snake_turns() {
+        let mut h = playing();
+        let (x, y) = h.game().snake.body[0];
+        let interval = h.game().move_interval;
+        let script = InputScript::new().press(h.tick(), Key::ArrowUp);
+        h.run(&script, interval * 2);
+        let snake = &h.game().snake;
+        assert_eq!(snake.dir, Dir::Up);
+        // up a row each step, and the neck follows the head
+        assert_eq!(snake.body[0], (x, y - 2));
+        assert_eq!(snake.body[1], (x, y - 1));
+        assert_eq!(snake.body.len(), 5);
+        let level_sprites = h.game().campaign.level().sprite_count();
+        let tile_sz = h.game().campaign.level().tile_size() as f32;
+        let corner = h.game().campaign.level().grid_to_world((x, y - 2));
+        // level, apple, then every body segment
+        let sink = h.render();
+        assert_eq!(sink.sprite_count(0), level_sprites + 1 + 5);
+        // "LV1" and "0/5" up top
+        assert_eq!(sink.sprite_count(TEXT_GROUP), 6);
+        // the head is drawn centered on its cell
+        let head = sink.transforms(0)[level_sprites + 1];
+        assert_eq!(
+            (head.x, head.y),
+            (corner.x + tile_sz / 2.0, corner.y + tile_sz / 2.0)
+        );
+        // facing up, up into the bend, then right along to the tail
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let segments: Vec<(u16, f32)> = sink.sheet_regions(0)[level_sprites + 1..]
+            .iter()
+            .zip(&sink.transforms(0)[level_sprites + 1..])
+            .map(|(region, trf)| (region.x, trf.rot))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                (SNAKE_SKIN.head.x, quarter),
+                (SNAKE_SKIN.straight.x, quarter),
+                (SNAKE_SKIN.corner.x, 3.0 * quarter),
+                (SNAKE_SKIN.straight.x, 0.0),
+                (SNAKE_SKIN.tail.x, 2.0 * quarter),
+            ]
+        );
+    }
+    #[test]
+    fn test_quick_turns_are_buffered() {
+        let mut h = playing();
+        let (x, y) = h.game().snake.body[0];
+        let interval = h.game().move_interval;
+        // up then left within a single step: both turns happen, one a step
+        let t = h.tick();
+        let script = InputScript::new()
+            .tap(t, Key::ArrowUp)
+            .tap(t + 1, Key::ArrowLeft);
+        h.run(&script, interval * 2);
+        let snake = &h.game().snake;
+        assert_eq!(snake.dir, Dir::Left);
+        assert_eq!(snake.body[0], (x - 1, y - 1));
+        // a tapped reversal is dropped rather than killing the snake
+        let script = InputScript::new().tap(h.tick(), Key::ArrowRight);
+        h.run(&script, interval);
+        assert_eq!(h.game().snake.dir, Dir::Left);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+    }
+    #[test]
+    fn test_replays_are_exact() {
+        let mut h = playing();
+        let (x, y) = h.game().snake.body[0];
+        let (ax, ay) = h.game().apple.pos;
+        assert!(ax < x);
+        // steer to the apple: up or down to its row, then left along it
+        let interval = h.game().move_interval;
+        let vertical = if ay < y { Key::ArrowUp } else { Key::ArrowDown };
+        let t = h.tick();
+        let script = InputScript::new()
+            .tap(t, vertical)
+            .tap(t + interval * y.abs_diff(ay) + 1, Key::ArrowLeft);
+        h.run(&script, interval * (y.abs_diff(ay) + x - ax + 1));
+        assert_eq!(h.game().score, 1);
+        // the apple was moved by the RNG, and lands in the same place again
+        let replay = engine::replay::Replay::parse(&h.replay().to_string()).unwrap();
+        let played = Headless::<Game>::play(engine::content!(), &replay);
+        assert_eq!(played.tick(), h.tick());
+        assert_eq!(played.game().score, 1);
+        assert_eq!(played.game().snake.body, h.game().snake.body);
+        assert_eq!(played.game().apple.pos, h.game().apple.pos);
+    }
+    #[test]
+    fn test_snake_eats_apple() {
+        let mut h = playing();
+        let (x, y) = h.game().snake.body[0];
+        h.game_mut().apple.pos = (x + 1, y);
+        let interval = h.game().move_interval;
+        h.run(&InputScript::new(), interval);
+        let game = h.game();
+        assert_eq!(game.snake.body[0], (x + 1, y));
+        assert_eq!(game.snake.body.len(), SNAKE_LEN + 1);
+        // the new apple is on an open cell off the snake
+        let apple = game.apple.pos;
+        assert!(!game.campaign.level().get_tile(apple).unwrap().solid);
+        assert!(!game.snake.body.contains(&apple));
+        // the tail waits until the snake has grown by a whole apple
+        h.game_mut().apple.pos = (x, y + 1);
+        h.run(&InputScript::new(), interval * 3);
+        let game = h.game();
+        assert_eq!(game.snake.body[0], (x + 4, y));
+        assert_eq!(game.snake.body.len(), SNAKE_LEN + APPLE_GROWTH);
+    }
+    #[test]
+    fn test_walls_are_deadly() {
+        let mut h = playing();
+        let start = h.game().snake.body[0];
+        // keep the apple out of the way
+        h.game_mut().apple.pos = (start.0, start.1 + 1);
+        let interval = h.game().move_interval;
+        let steps_to_wall = h.game().campaign.level().width() - 2 - start.0;
+        h.run(&InputScript::new(), interval * steps_to_wall);
+        assert_eq!(h.game().snake.body[0], (start.0 + steps_to_wall, start.1));
+        // the border column is solid
+        h.run(&InputScript::new(), interval);
+        assert_eq!(
+            h.game().scenes.top(),
+            Some(&Screen::GameOver { score: 0, level: 1 })
+        );
+        // and trying again starts over
+        h.run(&InputScript::new().tap(h.tick(), Key::Space), 2);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        assert_eq!(h.game().snake.body[0], start);
+    }
+    #[test]
+    fn test_save_and_resume() {
+        let mut h = playing();
+        let (x, y) = h.game().snake.body[0];
+        h.game_mut().apple.pos = (x + 1, y);
+        let interval = h.game().move_interval;
+        h.run(&InputScript::new(), interval * 3);
+        h.quit();
+        let body = h.game().snake.body.clone();
+        let apple = h.game().apple.pos;
 
-    // // sort player contacts vector
-    // em_contacts.sort_by(|a, b| b.overlap.mag_sq().partial_cmp(&a.overlap.mag_sq()).unwrap());
+        // closing the window on the title screen keeps the save
+        let mut h = Headless::<Game>::with_storage(engine::content!(), h.game().storage.clone());
+        assert!(h.game().saved.is_some());
+        h.quit();
+        let mut h = Headless::<Game>::with_storage(engine::content!(), h.game().storage.clone());
+        assert!(h.game().saved.is_some());
+        h.run(&InputScript::new().tap(h.tick(), Key::Space), 1);
+        let game = h.game();
+        assert_eq!(game.scenes.top(), Some(&Screen::Playing));
+        assert_eq!((game.score, game.progress.apples), (1, 1));
+        assert_eq!(game.snake.body, body);
+        assert_eq!(game.apple.pos, apple);
 
-    // // deal with player-tile contact
-    // for c in pl_contacts.drain(..) {
-    //     let displacement: Vec2 = compute_displacement(self.player.to_rect(), c.rect_b);
-    //     self.player.pos += displacement;
-    // }
+        // crashing ends that game and makes the high score table
+        h.game_mut().snake.body = VecDeque::from([(1, y)]);
+        h.game_mut().snake.dir = Dir::Left;
+        h.run(&InputScript::new(), interval);
+        assert!(matches!(
+            h.game().scenes.top(),
+            Some(Screen::GameOver { .. })
+        ));
+        h.quit();
+        let storage = h.game().storage.clone();
+        let h = Headless::<Game>::with_storage(engine::content!(), storage.clone());
+        let game = h.game();
+        assert!(game.saved.is_none());
+        assert_eq!(game.high_scores.best(game.campaign.name()), Some(1));
 
-    // for c in pl_en_contacts.drain(..) {
-    //     if attacking && !enemies_to_remove.contains(&c.index_b) {
-    //         enemies_to_remove.push(c.index_b);
-    //     }
-    // }
-    // enemies_to_remove.sort();
-    // for index in enemies_to_remove.iter().rev() {
-    //     self.enemies.swap_remove(*index);
-    // }
+        // broken saves start a new game rather than failing
+        let mut storage = storage;
+        storage
+            .write("snapshot.txt", "snapshot snake 99\n")
+            .unwrap();
+        let h = Headless::<Game>::with_storage(engine::content!(), storage.clone());
+        assert!(h.game().saved.is_none());
+        let header = format!("snapshot snake {SNAPSHOT_VERSION}\n");
+        storage
+            .write("snapshot.txt", &format!("{header}body 1 2 3\n"))
+            .unwrap();
+        let mut h = Headless::<Game>::with_storage(engine::content!(), storage);
+        h.run(&InputScript::new().tap(h.tick(), Key::Space), 1);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        assert_eq!(h.game().snake.body.len(), SNAKE_LEN);
 
-    fn compute_displacement(a: Rect, b: Rect) -> Vec2 {
-        let Some(mut overlap) = a.overlap(b) else {
-            return Vec2 { x: 0.0, y: 0.0 };
+        // and so do saves with a snake or apple off the map, or a rival
+        // with no body
+        let good = h.game().snapshot();
+        let body = h.game().snake.body.clone();
+        let (w, ht) = {
+            let level = h.game().campaign.level();
+            (level.width(), level.height())
         };
-        if overlap.y < overlap.x {
-            overlap.x = 0.0;
-        } else {
-            overlap.y = 0.0;
-        }
-        if a.x < b.x {
-            overlap.x *= -1.0;
+        for (key, cells) in [
+            ("body", vec![(1, 1), (w, 1)]),
+            ("apple", vec![(1, ht)]),
+            ("rival_body", vec![]),
+            ("rival_body", vec![(2, 2), (w + 3, 0)]),
+        ] {
+            let mut snapshot = good.clone();
+            snapshot.set_pairs(key, cells);
+            snapshot.set("rival_dir", Dir::Left);
+            snapshot.set("rival_growing", 0);
+            let err = h.game_mut().restore(&snapshot).unwrap_err();
+            assert!(
+                matches!(&err, SaveError::BadValue { key: k, .. } if k == key),
+                "{err}"
+            );
         }
-        if a.y < b.y {
-            overlap.y *= -1.0;
+        assert_eq!(h.game().snake.body, body);
+    }
+    #[test]
+    fn test_pause() {
+        let mut h = playing();
+        let start = h.game().snake.body[0];
+        let interval = h.game().move_interval;
+        h.run(&InputScript::new().tap(h.tick(), Key::Escape), interval * 3);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Paused));
+        assert_eq!(h.game().snake.body[0], start);
+        // the paused game still shows under the pause message
+        let level_sprites = h.game().campaign.level().sprite_count();
+        assert_eq!(h.render().sprite_count(0), level_sprites + 1 + SNAKE_LEN);
+        h.run(&InputScript::new().tap(h.tick(), Key::KeyP), interval * 2);
+        assert_eq!(h.game().scenes.top(), Some(&Screen::Playing));
+        assert_ne!(h.game().snake.body[0], start);
+    }
+    #[test]
+    fn test_level_up() {
+        let mut h = playing();
+        let goal = h.game().campaign.goals().apples.unwrap();
+        let (x, y) = h.game().snake.body[0];
+        h.game_mut().progress.apples = goal - 1;
+        h.game_mut().apple.pos = (x + 1, y);
+        let interval = h.game().move_interval;
+        h.run(&InputScript::new(), interval);
+        assert_eq!(h.game().progress.apples, goal);
+        // the goal is checked at the start of the next tick
+        h.step();
+        let game = h.game();
+        assert_eq!(game.campaign.index(), 1);
+        assert_eq!(game.progress, Progress::default());
+        assert_eq!(game.snake.body.len(), SNAKE_LEN);
+        assert_eq!(
+            game.snake.body[0],
+            start_cell(game.campaign.level(), Entity::Snake)
+        );
+    }
+    #[test]
+    fn test_rival_races_for_apples() {
+        assert!(
+            playing().game().rival.is_none(),
+            "rivals are off by default"
+        );
+        let mut h = Headless::<Game>::new(engine::content!());
+        h.input_mut().press(Key::KeyR);
+        h.step();
+        h.input_mut().release(Key::KeyR);
+        h.input_mut().press(Key::Space);
+        h.step();
+        h.input_mut().release(Key::Space);
+        assert_eq!(h.game().rival.as_ref().unwrap().body[0], (24, 25));
+        // an apple behind the rival, with the player far away
+        h.game_mut().apple.pos = (20, 27);
+        h.game_mut().snake.dir = Dir::Down;
+        let interval = h.game().move_interval;
+        for _ in 0..10 {
+            if h.game().apple.pos != (20, 27) {
+                break;
+            }
+            h.run(&InputScript::new(), interval);
         }
-        return overlap;
+        let game = h.game();
+        assert_eq!(game.scenes.top(), Some(&Screen::Playing));
+        let rival = game.rival.as_ref().unwrap();
+        assert!(rival.body.contains(&(20, 27)));
+        assert_eq!(rival.body.len(), SNAKE_LEN + 1);
+        // apples the rival eats don't count for the player
+        assert_eq!((game.progress.apples, game.score), (0, 0));
     }
 }